@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Clone,Debug)] 
 pub enum Program {
     Body{statements: Vec<Statement>},
@@ -7,21 +9,69 @@ pub enum Program {
 pub enum Statement {
     Import{path: String},
     Expression{expression: Expression},
-    Assignment{lhs: Expression, rhs: Expression},
-    OperatorAssignment{name: String, 
+    Assignment{lhs: Expression, 
+               annotation: Option<Type>, 
+               rhs: Expression, 
+               location: Location},
+    // `let` and `const` bind names in the innermost block. The names of a
+    // const can't be assigned to again
+    Declaration{lhs: Expression, 
+                annotation: Option<Type>, 
+                rhs: Expression, 
+                is_const: bool, 
+                location: Location},
+    OperatorAssignment{lhs: Expression, 
                        operator: Operator, 
-                       rhs: Expression},
+                       rhs: Expression, 
+                       location: Location},
     
     If{params: IfBranch},
     
-    While{condition: Expression, statements: Vec<Statement>},
+    While{condition: Expression, 
+          statements: Vec<Statement>, 
+          label: Option<String>},
     
     For{params: ForLoop},
 
     FunctionDefinition{name: String, 
-                       arguments: Vec<String>, 
+                       arguments: Vec<Parameter>, 
+                       returns: Option<Type>,
                        statements: Vec<Statement>,
-                       return_expression: Option<Expression> },
+                       location: Location},
+
+    StructDefinition{name: String, fields: Vec<String>},
+    EnumDefinition{name: String, variants: Vec<EnumVariant>},
+
+    Match{params: MatchBlock},
+
+    Return{expression: Option<Expression>, location: Location},
+    Throw{expression: Expression, location: Location},
+    // Assignments to these names inside a function change the program's
+    // variable, or the nearest enclosing one for nonlocal
+    Global{names: Vec<String>},
+    Nonlocal{names: Vec<String>, location: Location},
+    Try{statements: Vec<Statement>, 
+        catch: Option<CatchBlock>, 
+        finally: Option<Vec<Statement>>},
+    Break{label: Option<String>},
+    Continue{label: Option<String>},
+}
+
+impl Statement {
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            Statement::Assignment { location, .. }
+            | Statement::OperatorAssignment { location, .. }
+            | Statement::Declaration { location, .. }
+            | Statement::FunctionDefinition { location, .. }
+            | Statement::Return { location, .. }
+            | Statement::Throw { location, .. }
+            | Statement::Nonlocal { location, .. } => Some(*location),
+            Statement::For { params } => Some(params.location),
+            Statement::Expression { expression } => expression.location(),
+            _ => None
+        }
+    }
 }
 
 #[derive(Clone,Debug)] 
@@ -33,25 +83,170 @@ pub enum Expression {
     Float{f: f64},
     Character{c: char},
     List{items: Vec<ListItem>},
+    Map{entries: Vec<(Expression, Expression)>},
+    FString{parts: Vec<FStringPart>},
     // END TYPES
 
-    Identifier{name: String},
-    Call{function: String, arguments: Vec<Expression>},
+    Identifier{name: String, location: Location},
+    Call{function: Box<Expression>, 
+         arguments: Vec<Argument>, 
+         location: Location},
+
+    Operation{lhs: Box<Expression>, 
+              rhs: Box<Expression>, 
+              operator: Operator, 
+              location: Location},
+    Prefix{name: String, 
+           operator:Operator, 
+           rhs: Box<Expression>, 
+           location: Location},
+    Not{rhs: Box<Expression>},
+
+    Index{expression: Box<Expression>, idx_exp: Box<Expression>},
+    Slice{expression: Box<Expression>, 
+          start: Option<Box<Expression>>, 
+          end: Option<Box<Expression>>, 
+          step: Option<Box<Expression>>},
 
-    Operation{lhs: Box<Expression>, rhs: Box<Expression>, operator: Operator},
-    Prefix{name: String, operator:Operator, rhs: Box<Expression>},
+    StructLiteral{name: String, fields: Vec<(String, Expression)>},
+    Field{expression: Box<Expression>, field: String},
 
-    Index{name: String, idx_exp: Box<Expression>},
+    Lambda{arguments: Vec<Parameter>, 
+           returns: Option<Type>, 
+           statements: Vec<Statement>, 
+           location: Location},
 
     Comprehension{iterate_exp: Box<Expression>, 
-                  var: String, 
-                  control_exp: Box<Expression>}
+                  clauses: Vec<ComprehensionClause>},
+    MapComprehension{key: Box<Expression>, 
+                     value: Box<Expression>, 
+                     clauses: Vec<ComprehensionClause>},
+
+    Match{params: Box<MatchBlock>},
+}
+
+impl Expression {
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            Expression::Identifier { location, .. }
+            | Expression::Call { location, .. }
+            | Expression::Operation { location, .. }
+            | Expression::Lambda { location, .. }
+            | Expression::Prefix { location, .. } => Some(*location),
+            _ => None
+        }
+    }
+}
+
+// A rest parameter, written `..rest`, is always last and collects the
+// positional arguments left over as a list
+#[derive(Clone,Debug)] 
+pub struct Parameter {
+    pub name: String,
+    pub annotation: Option<Type>,
+    pub default: Option<Expression>,
+    pub is_rest: bool,
+}
+
+// An argument in a call, passed by position or by the parameter's name. A
+// spread list gives several positional arguments and a spread map gives
+// named ones
+#[derive(Clone,Debug)] 
+pub struct Argument {
+    pub name: Option<String>,
+    pub expression: Expression,
+    pub is_spread: bool,
+}
+
+// A type written after `:` or `->`. Names other than the builtin ones refer
+// to structs and enums
+#[derive(Clone,Debug,PartialEq)] 
+pub enum Type {
+    Int,
+    Float,
+    Str,
+    Bool,
+    Char,
+    List,
+    Map,
+    Range,
+    Function,
+    Null,
+    Any,
+    Named{name: String},
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Type::Int => "int",
+            Type::Float => "float",
+            Type::Str => "str",
+            Type::Bool => "bool",
+            Type::Char => "char",
+            Type::List => "list",
+            Type::Map => "map",
+            Type::Range => "range",
+            Type::Function => "fn",
+            Type::Null => "null",
+            Type::Any => "any",
+            Type::Named { name } => name,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Where a statement or expression starts in its file, counted from 1. Kept in
+// the AST so errors can point at the code that caused them
+#[derive(Clone,Copy,Debug,PartialEq)] 
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+// The offsets where each line of a source file starts, to turn the byte
+// offsets the parser gives into locations
+pub struct Lines<'a> {
+    source: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+    pub fn new(source: &'a str) -> Lines<'a> {
+        let breaks = source.match_indices('\n').map(|(idx, _)| idx + 1);
+        Lines { source, starts: std::iter::once(0).chain(breaks).collect() }
+    }
+
+    pub fn location(&self, offset: usize) -> Location {
+        let line = self.starts.partition_point(|&start| start <= offset);
+        let start = self.starts[line - 1];
+        let column = self.source.get(start..offset)
+            .map_or(offset - start, |text| text.chars().count());
+        Location { line, column: column + 1 }
+    }
 }
+
 #[derive(Clone,Debug)] 
 pub struct ForLoop {
+    pub label: Option<String>,
     pub loop_var: String,
     pub iterate_expression: Expression,
     pub statements: Vec<Statement>,
+    pub location: Location,
+}
+
+// The `for` and `if` parts of a comprehension, applied left to right. Each
+// `for` runs once for every combination of items of the ones before it
+#[derive(Clone,Debug)] 
+pub enum ComprehensionClause {
+    For{target: Expression, iterable: Expression},
+    If{condition: Expression},
 }
 
 #[derive(Clone,Debug)] 
@@ -62,6 +257,63 @@ pub struct IfBranch {
     pub elif_data: (Vec<Expression>, Vec<Vec<Statement>>),
 }
 
+// The name the caught error is bound to, and the statements that handle it
+#[derive(Clone,Debug)] 
+pub struct CatchBlock {
+    pub name: String,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Clone,Debug)] 
+pub struct EnumVariant {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+#[derive(Clone,Debug)] 
+pub struct MatchBlock {
+    pub subject: Expression,
+    pub arms: Vec<MatchArm>,
+}
+
+// Patterns are parsed as expressions and only interpreted as patterns when
+// the match runs, the same way assignment targets are
+#[derive(Clone,Debug)] 
+pub struct MatchArm {
+    pub pattern: Expression,
+    pub body: MatchBody,
+}
+
+#[derive(Clone,Debug)] 
+pub enum MatchBody {
+    Expression{expression: Expression},
+    Block{statements: Vec<Statement>},
+}
+
+#[derive(Clone,Debug)] 
+pub enum FStringPart {
+    Text{s: String},
+    Value{expression: Expression, spec: Option<FormatSpec>},
+}
+
+// The part of `{pi:>8.3}` after the colon: an optional fill character and
+// alignment, a 0 flag to pad numbers with zeros, a width and a precision
+#[derive(Clone,Debug)] 
+pub struct FormatSpec {
+    pub fill: char,
+    pub align: Option<Align>,
+    pub zero: bool,
+    pub width: usize,
+    pub precision: Option<usize>,
+}
+
+#[derive(Clone,Debug)] 
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
 #[derive(Clone,Debug)] 
 pub struct ListItem {
     pub expression: Expression,
@@ -75,8 +327,40 @@ pub enum Operator {
     Minus,
     Times,
     Divide,
+    Modulo,
+    Power,
+    IntDivide,
     LessThan,
     GreaterThan,
+    LessEqual,
+    GreaterEqual,
     Equal,
-    NotEqual
+    NotEqual,
+    And,
+    Or,
+    In,
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Times => "*",
+            Operator::Divide => "/",
+            Operator::Modulo => "%",
+            Operator::Power => "**",
+            Operator::IntDivide => "~/",
+            Operator::LessThan => "<",
+            Operator::GreaterThan => ">",
+            Operator::LessEqual => "<=",
+            Operator::GreaterEqual => ">=",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::In => "in",
+        };
+        write!(f, "{}", symbol)
+    }
 }
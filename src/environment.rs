@@ -0,0 +1,196 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::value::Value;
+
+// A handle to one scope in a chain of scopes. Cloning the handle shares the
+// scope, which is how functions keep a reference to where they were defined
+#[derive(Clone, Default)]
+pub struct Environment {
+    scope: Rc<RefCell<Scope>>,
+}
+
+// Blocks are the bodies of ifs, loops, try statements and match arms. The
+// other scopes belong to the program, a function call or a comprehension
+#[derive(Default)]
+struct Scope {
+    values: HashMap<String, Value>,
+    constants: HashSet<String>,
+    // Names made global or nonlocal, with the scope that binds each one
+    outer: HashMap<String, Environment>,
+    parent: Option<Environment>,
+    is_block: bool,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    // Creates a new, empty scope that falls back to this one for lookups
+    pub fn child(&self) -> Environment {
+        Environment {
+            scope: Rc::new(RefCell::new(Scope {
+                parent: Some(self.clone()),
+                ..Scope::default()
+            }))
+        }
+    }
+
+    // Like child, but names assigned without let or const inside a block
+    // still go to the scope around it
+    pub fn block(&self) -> Environment {
+        Environment {
+            scope: Rc::new(RefCell::new(Scope {
+                parent: Some(self.clone()),
+                is_block: true,
+                ..Scope::default()
+            }))
+        }
+    }
+
+    // Looks a name up in this scope, then in each enclosing scope
+    pub fn get(&self, name: &str) -> Option<Value> {
+        let scope = self.scope.borrow();
+        if let Some(target) = scope.outer.get(name) {
+            return target.get(name);
+        }
+        match scope.values.get(name) {
+            Some(v) => Some(v.clone()),
+            None => scope.parent.as_ref()?.get(name)
+        }
+    }
+
+    // The names that can be read from this scope
+    pub fn names(&self) -> Vec<String> {
+        let scope = self.scope.borrow();
+        let mut names: Vec<String> = scope.values.keys()
+            .chain(scope.outer.keys())
+            .cloned()
+            .collect();
+        if let Some(parent) = &scope.parent {
+            names.extend(parent.names());
+        }
+        names
+    }
+
+    // Binds a name in this scope, shadowing any enclosing binding
+    pub fn insert(&self, name: String, value: Value) {
+        self.scope.borrow_mut().values.insert(name, value);
+    }
+
+    // Binds a name for `let` or `const`, which can only happen once per scope
+    pub fn declare(&self, name: String, value: Value, is_const: bool) 
+        -> Result<(), Error> {
+        let mut scope = self.scope.borrow_mut();
+        if scope.values.contains_key(&name) {
+            return Err(Error::name_error(format!(
+                "'{}' is already declared in this scope", name)));
+        }
+        if is_const {
+            scope.constants.insert(name.clone());
+        }
+        scope.values.insert(name, value);
+        Ok(())
+    }
+
+    // Gives a name a new value where it is bound, looking outwards through
+    // blocks but not past the enclosing function. A name that isn't bound
+    // yet is bound in the function's scope, unless the function declared it
+    // global or nonlocal
+    pub fn assign(&self, name: String, value: Value) -> Result<(), Error> {
+        let mut scope = self.scope.borrow_mut();
+        if let Some(target) = scope.outer.get(&name).cloned() {
+            drop(scope);
+            return target.assign(name, value);
+        }
+        if scope.values.contains_key(&name) || !scope.is_block {
+            if scope.constants.contains(&name) {
+                return Err(Error::type_error(format!(
+                    "Cannot assign to constant '{}'", name)));
+            }
+            scope.values.insert(name, value);
+            return Ok(());
+        }
+
+        let parent = scope.parent.clone()
+            .expect("blocks are always inside another scope");
+        drop(scope);
+        parent.assign(name, value)
+    }
+
+    // Whether the name is bound by `const` where it would be looked up
+    pub fn is_const(&self, name: &str) -> bool {
+        self.owner(name)
+            .is_some_and(|env| env.scope.borrow().constants.contains(name))
+    }
+
+    // Sends the current function's uses of a name to the program's scope.
+    // At the top level the name is already global, so nothing changes
+    pub fn global(&self, name: String) {
+        let function = self.function_scope();
+        let root = self.root();
+        if !Rc::ptr_eq(&function.scope, &root.scope) {
+            function.scope.borrow_mut().outer.insert(name, root);
+        }
+    }
+
+    // Sends the current function's uses of a name to the nearest enclosing
+    // scope that binds it
+    pub fn nonlocal(&self, name: String) -> Result<(), Error> {
+        let function = self.function_scope();
+        let parent = function.scope.borrow().parent.clone();
+        let Some(target) = parent.and_then(|p| p.owner(&name)) else {
+            return Err(Error::name_error(format!(
+                "No binding for nonlocal '{}' found", name)));
+        };
+        function.scope.borrow_mut().outer.insert(name, target);
+        Ok(())
+    }
+
+    // The innermost scope that isn't a block
+    fn function_scope(&self) -> Environment {
+        let scope = self.scope.borrow();
+        match &scope.parent {
+            Some(parent) if scope.is_block => parent.function_scope(),
+            _ => self.clone()
+        }
+    }
+
+    fn root(&self) -> Environment {
+        match &self.scope.borrow().parent {
+            Some(parent) => parent.root(),
+            None => self.clone()
+        }
+    }
+
+    // The scope a name would be looked up in
+    fn owner(&self, name: &str) -> Option<Environment> {
+        let scope = self.scope.borrow();
+        if let Some(target) = scope.outer.get(name) {
+            return Some(target.clone());
+        }
+        if scope.values.contains_key(name) {
+            return Some(self.clone());
+        }
+        scope.parent.as_ref()?.owner(name)
+    }
+}
+
+// Scopes can hold functions that refer back to the scope itself, so only the
+// names are printed to avoid recursing forever
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scope = self.scope.borrow();
+        let mut names: Vec<&String> = scope.values.keys().collect();
+        names.sort();
+
+        f.debug_struct("Environment")
+            .field("names", &names)
+            .field("parent", &scope.parent)
+            .finish()
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt;
+
+use crate::ast::Location;
+use crate::value::Value;
+
+// A failure while the program runs. It unwinds through the enclosing blocks
+// and calls until a try statement catches it as an Error value. The kind
+// tells failures apart, like the NameError of an undefined variable
+#[derive(Clone, Debug)]
+pub struct Error {
+    pub kind: String,
+    pub message: String,
+    pub location: Option<Location>,
+    // What `throw` was given, null for failures of the interpreter
+    pub value: Box<Value>,
+}
+
+impl Error {
+    pub fn new(kind: &str, message: String) -> Error {
+        Error {
+            kind: kind.to_string(),
+            message,
+            location: None,
+            value: Box::new(Value::Null)
+        }
+    }
+
+    pub fn name_error(message: String) -> Error {
+        Error::new("NameError", message)
+    }
+
+    pub fn type_error(message: String) -> Error {
+        Error::new("TypeError", message)
+    }
+
+    pub fn value_error(message: String) -> Error {
+        Error::new("ValueError", message)
+    }
+
+    pub fn index_error(message: String) -> Error {
+        Error::new("IndexError", message)
+    }
+
+    pub fn key_error(message: String) -> Error {
+        Error::new("KeyError", message)
+    }
+
+    pub fn field_error(message: String) -> Error {
+        Error::new("FieldError", message)
+    }
+
+    pub fn zero_division(message: String) -> Error {
+        Error::new("ZeroDivisionError", message)
+    }
+
+    // Errors keep the first location they are given, which belongs to the
+    // innermost statement or expression around the failure
+    pub fn at(mut self, location: Option<Location>) -> Error {
+        if self.location.is_none() {
+            self.location = location;
+        }
+        self
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Error {
+        Error::new("Error", message)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
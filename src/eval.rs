@@ -1,106 +1,163 @@
-use std::collections::HashMap;
-use std::env::{ args, current_dir, var};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::env::{current_dir, var};
 use std::path::Path;
 
-use crate::ast::{Expression, IfBranch, ListItem, Operator, Program, Statement};
-use crate::parser::ProgramParser;
-use crate::read_file;
-use crate::value::Value;
+use crate::environment::Environment;
+use crate::error::Error;
+use crate::ast::{Argument, ComprehensionClause, EnumVariant, Expression, 
+                 FStringPart, IfBranch, ListItem, MatchBlock, MatchBody, 
+                 Operator, Parameter, Program, Statement};
+use crate::{parse, read_file};
+use crate::typecheck::check_program;
+use crate::value::{range_item, shared, MapKey, Value, ValueIterator};
+
+// How control leaves a statement. Anything other than Next unwinds through
+// the enclosing blocks until something handles it
+enum ControlFlow {
+    Next,
+    Return(Value),
+    Break(Option<String>),
+    Continue(Option<String>),
+}
+
+impl ControlFlow {
+    // Turns a signal that escaped the construct meant to handle it into an
+    // error
+    fn escaped(&self) -> String {
+        match self {
+            ControlFlow::Next => "dev error: ".to_string(),
+            ControlFlow::Return(_) 
+                => "'return' outside of a function".to_string(),
+            ControlFlow::Break(None) 
+                => "'break' outside of a loop".to_string(),
+            ControlFlow::Continue(None) 
+                => "'continue' outside of a loop".to_string(),
+            ControlFlow::Break(Some(label)) 
+            | ControlFlow::Continue(Some(label)) 
+                => format!("No enclosing loop labeled '{}'", label),
+        }
+    }
+}
 
-pub fn eval_program(enviornment: &mut HashMap<String, Value>, 
-                    Program::Body{statements}: &Program, importing: bool) 
-                    -> Result<(), String> {
+// The file being run, which relative imports are found from, whether it
+// is being imported by another file, and whether imports are checked with
+// --strict
+#[derive(Clone, Copy)]
+pub struct Source<'a> {
+    pub path: &'a str,
+    pub importing: bool,
+    pub strict: bool,
+}
+
+pub fn eval_program(enviornment: &Environment, 
+                    Program::Body{statements}: &Program, source: Source) 
+                    -> Result<(), Error> {
         
-        eval_statements(enviornment, statements, importing)
+        match eval_statements(enviornment, statements, source)? {
+            ControlFlow::Next => Ok(()),
+            flow => Err(flow.escaped().into()),
+        }
+}
+
+// How assign binds the names it is given. Plain assignment updates a name
+// where it is already bound, the others declare it in the innermost scope
+#[derive(Clone, Copy)]
+enum Binding {
+    Assign,
+    Let,
+    Const,
 }
 
-fn assign(enviornment: &mut HashMap<String, Value>, lhs: Expression, rhs: Value)
-    -> Result<(), String> {
+fn assign(enviornment: &Environment, lhs: Expression, rhs: Value, 
+          binding: Binding, source: Source) -> Result<(), Error> {
 
     match lhs {
-        Expression::Identifier { name } => {
+        Expression::Identifier { name, .. } => {
                     if name == "_" {
                         return Ok(());
                     }
-                    enviornment.insert(name.clone(), rhs);
+                    match binding {
+                        Binding::Assign => enviornment.assign(name, rhs)?,
+                        Binding::Let 
+                            => enviornment.declare(name, rhs, false)?,
+                        Binding::Const 
+                            => enviornment.declare(name, rhs, true)?,
+                    }
         },
         Expression::List { items } => {
             let Value::List{e: new_items} = rhs 
             else { 
-                return Err("cannot destructure non-list into list".to_string()) 
+                return Err(Error::type_error(
+                    "cannot destructure non-list into list".to_string())) 
             };
 
-            assign_list(enviornment, items, new_items)?;
+            let new_items = new_items.borrow().clone();
+            assign_list(enviornment, items, new_items, binding, source)?;
         },
-        Expression::Index { name, idx_exp} => {
+        Expression::Index { .. } | Expression::Slice { .. } 
+        | Expression::Field { .. } if matches!(binding, Binding::Assign) => {
+            let (name, steps) = place(enviornment, &lhs, source)?;
             let Some(var) = enviornment.get(&name) 
-                else { return Err(format!("'{}' is not defined", name)) };
-            
-
-            let exp_res = 
-                match eval_expression(&mut enviornment.clone(), 
-                          &idx_exp, false){
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-            };
-
-            let mut list = match var {
-                Value::List { e } => e.clone(),
-
-                Value::Str { .. } 
-                    => return Err("Cannot assign to String Index".to_string()),
-                Value::Null 
-                    => return Err("Cannot index Null".to_string()),
-                Value::Int { .. } 
-                    => return Err("Cannot index Int".to_string()),
-                Value::Bool { .. } 
-                    => return Err("Cannot index Boolean".to_string()),
-                Value::Char { .. } 
-                    => return Err("Cannot index Char".to_string()),
-                Value::Function { .. } 
-                    => return Err("Cannot index Function".to_string()),
-                Value::UserDefFunction { .. } 
-                    => return Err("Cannot index Function".to_string()),
-                Value::Float { .. } 
-                    => return Err("Cannot index Float".to_string()),
-            };
-
-            let Value::Int { v: idx } = exp_res 
-                else { return Err("Index must be of type int".to_string()) };
-
-            let usize_idx = idx.unsigned_abs() as usize;
-            let length = list.len();
-            if usize_idx > length {
-                return Err(format!("Index {} is out of bounds", idx));
+                else { return Err(Error::name_error(
+                    format!("'{}' is not defined", name))) };
+            if enviornment.is_const(&name) {
+                return Err(Error::type_error(format!(
+                    "Cannot assign to constant '{}'", name)));
             }
 
-            if idx < 0 {
-                list[length - usize_idx] = rhs;
-            }else{
-                list[usize_idx] = rhs;
-            }
-            
-
-            enviornment.insert(name, Value::List { e: list });
-        }
+            set_path(var, &steps, rhs)?;
+        },
+        Expression::Index { .. } | Expression::Slice { .. } 
+        | Expression::Field { .. } 
+            => return Err("Only names can be declared with let or const"
+                          .to_string().into()),
         Expression::Int { .. } 
-            => return Err("Cannot assign to a Integer literal".to_string()),
+            => return Err(Error::type_error(
+                "Cannot assign to a Integer literal".to_string())),
         Expression::String { .. } 
-            => return Err("Cannot assign to a String literal".to_string()),
+            => return Err(Error::type_error(
+                "Cannot assign to a String literal".to_string())),
         Expression::Boolean { ..} 
-            => return Err("Cannot assign to a Boolean literal".to_string()),
+            => return Err(Error::type_error(
+                "Cannot assign to a Boolean literal".to_string())),
         Expression::Float { .. } 
-            => return Err("Cannot assign to a Float literal".to_string()),
+            => return Err(Error::type_error(
+                "Cannot assign to a Float literal".to_string())),
         Expression::Character { .. } 
-            => return Err("Cannot assign to a Character literal".to_string()),
+            => return Err(Error::type_error(
+                "Cannot assign to a Character literal".to_string())),
         Expression::Call { ..} 
-            => return Err("Cannot assign to a Function call".to_string()),
+            => return Err(Error::type_error(
+                "Cannot assign to a Function call".to_string())),
         Expression::Operation { .. } 
-            => return Err("Cannot assign to a Operation".to_string()),
+            => return Err(Error::type_error(
+                "Cannot assign to a Operation".to_string())),
         Expression::Prefix { .. } 
-            => return Err("Cannot assign to a Prefix".to_string()),
+            => return Err(Error::type_error(
+                "Cannot assign to a Prefix".to_string())),
+        Expression::Not { .. } 
+            => return Err(Error::type_error(
+                "Cannot assign to a Not".to_string())),
         Expression::Comprehension { .. } 
-            => return Err("Cannot assign to a Comprehension".to_string()),
+        | Expression::MapComprehension { .. } 
+            => return Err(Error::type_error(
+                "Cannot assign to a Comprehension".to_string())),
+        Expression::Lambda { .. } 
+            => return Err(Error::type_error(
+                "Cannot assign to a Function".to_string())),
+        Expression::Map { .. } 
+            => return Err(Error::type_error(
+                "Cannot assign to a Map literal".to_string())),
+        Expression::FString { .. } 
+            => return Err(Error::type_error(
+                "Cannot assign to a String literal".to_string())),
+        Expression::StructLiteral { .. } 
+            => return Err(Error::type_error(
+                "Cannot assign to a Struct literal".to_string())),
+        Expression::Match { .. } 
+            => return Err(Error::type_error(
+                "Cannot assign to a Match".to_string())),
     }
 
 
@@ -108,13 +165,14 @@ fn assign(enviornment: &mut HashMap<String, Value>, lhs: Expression, rhs: Value)
     Ok(())
 }
 
-fn assign_list(enviornment: &mut HashMap<String, Value>, lhs: Vec<ListItem>, 
-    rhs: Vec<Value>) -> Result<(), String> {
+fn assign_list(enviornment: &Environment, lhs: Vec<ListItem>, 
+    rhs: Vec<Value>, binding: Binding, source: Source) -> Result<(), Error> {
 
     if lhs.len() > rhs.len() {
-        return Err(format!("Cannot assign {} values to {} items", 
+        return Err(Error::value_error(
+            format!("Cannot assign {} values to {} items", 
                     rhs.len(), 
-                    lhs.len()))
+                    lhs.len())))
     }
 
     let mut assign_name_queue: Vec<ListItem> = vec![];
@@ -123,18 +181,20 @@ fn assign_list(enviornment: &mut HashMap<String, Value>, lhs: Vec<ListItem>,
     for x in 0..rhs.len(){
         if x == lhs.len() - 1 && lhs.len() != rhs.len(){
             if !lhs[x].is_pack {
-                return Err(format!("Cannot assign {} values to {} items", 
-                    rhs.len(), 
-                    lhs.len()))
+                return Err(Error::value_error(
+                    format!("Cannot assign {} values to {} items", 
+                            rhs.len(), 
+                            lhs.len())))
             }
 
             assign_name_queue.push(lhs[x].clone());
-            assign_value_queue.push(Value::List{e: rhs[x..].to_vec()});
+            assign_value_queue.push(Value::List{e: shared(rhs[x..].to_vec())});
             break;
         }
 
         if lhs[x].is_spread {
-            return Err("Cannot use spread in list assignment".to_string())
+            return Err("Cannot use spread in list assignment".to_string()
+                           .into())
         }
 
         assign_name_queue.push(lhs[x].clone());
@@ -142,57 +202,82 @@ fn assign_list(enviornment: &mut HashMap<String, Value>, lhs: Vec<ListItem>,
     }
 
     for (ListItem{expression, .. }, value) in
-        assign_name_queue.into_iter().zip(assign_value_queue.into_iter()) {
+        assign_name_queue.into_iter().zip(assign_value_queue) {
         
-        assign(enviornment, expression, value)?;
+        assign(enviornment, expression, value, binding, source)?;
     }
 
     Ok(())
 
 }
 
-fn eval_statement(enviornment: &mut HashMap<String, Value>, 
-    statement: &Statement, importing: bool) -> Result<(), String> {
+fn eval_statement(enviornment: &Environment, 
+    statement: &Statement, source: Source) -> Result<ControlFlow, Error> {
     match statement {
         Statement::Expression{expression} => {
-            eval_expression(enviornment, expression, importing)?;
+            eval_expression(enviornment, expression, source)?;
         },
-        Statement::Assignment{lhs, rhs} => {
-            let v = 
-                match eval_expression(enviornment, rhs, importing) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-                };
+        Statement::Assignment{lhs, rhs, ..} => {
+            let v = eval_expression(enviornment, rhs, source)?;
             
-            assign(enviornment, lhs.clone(), v)?;
+            assign(enviornment, lhs.clone(), v, Binding::Assign, source)?;
         },
-        Statement::OperatorAssignment{name, operator, rhs} => {
-            let lhs = 
-                match enviornment.get(name) {
-                    Some(v) => v.clone(),
-                    None => return Err(format!("'{}' is not defined", &name))
-                };
+        Statement::Declaration{lhs, rhs, is_const, ..} => {
+            let v = eval_expression(enviornment, rhs, source)?;
+            let binding = if *is_const { Binding::Const } else { Binding::Let };
 
-            let rhs = match eval_expression(enviornment, rhs, importing) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e)
-                };
+            assign(enviornment, lhs.clone(), v, binding, source)?;
+        },
+        Statement::OperatorAssignment{lhs, operator, rhs, ..} => {
+            let (name, steps) = place(enviornment, lhs, source)?;
+            let Some(var) = enviornment.get(&name) 
+                else { return Err(Error::name_error(
+                    format!("'{}' is not defined", name))) };
+
+            let mut current = var.clone();
+            for step in &steps {
+                current = get_step(&current, step)?;
+            }
+
+            let rhs = eval_expression(enviornment, rhs, source)?;
+            // Like in Python, `+=` adds to a list in place. A slice is a new
+            // list, so adding to it is assigned back to the slice instead
+            let extend = match (operator, &current, &rhs, steps.last()) {
+                (Operator::Plus, Value::List { e }, Value::List { e: items }, 
+                 None | Some(PathStep::Index(_) | PathStep::Field(_))) 
+                    => Some((e, items.borrow().clone())),
+                _ => None
+            };
+            if (extend.is_some() || !steps.is_empty()) 
+                && enviornment.is_const(&name) {
+                return Err(Error::type_error(format!(
+                    "Cannot assign to constant '{}'", name)));
+            }
+            if let Some((e, items)) = extend {
+                e.borrow_mut().extend(items);
+                return Ok(ControlFlow::Next);
+            }
 
             let v = 
-                match operate(operator, &lhs, &rhs) {
+                match operate(operator, &current, &rhs) {
                     Ok(Value::Null) 
-                        => return Err(format!("Cannot operate on {}", name)),
+                        => return Err(Error::type_error(
+                            format!("Cannot operate on {}", name))),
                     Ok(v) => v,
                     Err(e) => return Err(e)
                 };
 
-            enviornment.insert(name.clone(), v);
+            if steps.is_empty() {
+                enviornment.assign(name, v)?;
+            } else {
+                set_path(var, &steps, v)?;
+            }
         },
         Statement::If{params} => {
-            match eval_expression(enviornment, &params.condition, importing) {
+            match eval_expression(enviornment, &params.condition, source) {
                 Ok(Value::Bool{b: true}) 
-                    => eval_statements(enviornment, &params.statements, 
-                                       importing)?,
+                    => return eval_statements(&enviornment.block(), 
+                                              &params.statements, source),
                 Ok(Value::Bool{b: false}) => {
                     let (elif_conditions, elif_statements ) = &params.elif_data;
                     if !elif_conditions.is_empty() {
@@ -207,111 +292,181 @@ fn eval_statement(enviornment: &mut HashMap<String, Value>,
                                         elif_statements[1..].to_vec())
                         };
 
-                        eval_statement(enviornment, 
-                            &Statement::If{params: next_iter}, importing)?;
+                        return eval_statement(enviornment, 
+                            &Statement::If{params: next_iter}, source);
                     }else if let Some(else_statements) = 
                         &params.else_statements { 
-                            eval_statements(enviornment, else_statements, 
-                                            importing)?;
+                            return eval_statements(&enviornment.block(), 
+                                                   else_statements, source);
                     }
                 },
-                _ => return Err("Condition must be of type 'bool'".to_string()),
+                Err(e) => return Err(e),
+                _ => return Err(Error::type_error(
+                    "Condition must be of type 'bool'".to_string())),
             }
         },
-        Statement::While{condition, statements} => {            
+        Statement::While{condition, statements, label} => {
             loop{
                 let b = 
-                    match eval_expression(enviornment, condition, importing) {
+                    match eval_expression(enviornment, condition, source) {
                         Ok(Value::Bool{b}) => b ,
                         Err(e) => return Err(e),
-                        _ => return Err(
-                            "Condition must be of type 'bool'".to_string()),
+                        _ => return Err(Error::type_error(
+                            "Condition must be of type 'bool'".to_string())),
                     };
                             
                 if !b { break; }
                 
-                if let Err(e) 
-                    = eval_statements(enviornment, statements, importing) {
-                    return Err(e);
+                match eval_statements(&enviornment.block(), statements, 
+                                      source)? {
+                    ControlFlow::Break(target) 
+                        if targets_loop(target.as_ref(), label.as_ref()) 
+                        => break,
+                    ControlFlow::Continue(target) 
+                        if targets_loop(target.as_ref(), label.as_ref()) 
+                        => continue,
+                    ControlFlow::Next => (),
+                    flow => return Ok(flow),
                 }
             }
         },
         Statement::For{params} => {
-            let v = 
-            match &params.iterate_expression {
-                Expression::List { .. } 
-                    => eval_expression(enviornment, 
-                                      &params.iterate_expression, importing)?,
-                Expression::Identifier { .. } 
-                    => eval_expression(enviornment, 
-                                      &params.iterate_expression, importing)?,
-                Expression::Call { .. } 
-                    => eval_expression(enviornment, 
-                                      &params.iterate_expression, importing)?,
-                Expression::Int { .. } 
-                    => return Err(
-                        "Integer literals are not iterable".to_string()),
-                Expression::String { .. } 
-                    => return Err(
-                        "String literals are not iterable".to_string()),
-                Expression::Boolean { .. } 
-                    => return Err(
-                        "Boolean literals are not iterable".to_string()),
-                Expression::Float { .. } 
-                    => return Err(
-                        "Float literals are not iterable".to_string()),
-                Expression::Character { .. } 
-                    => return Err(
-                        "Character literals are not iterable".to_string()),
-                Expression::Operation { .. } 
-                    => return Err(
-                        "Operations are not iterable".to_string()),
-                Expression::Prefix { .. } 
-                    => return Err(
-                        "Prefix's are not iterable".to_string()),
-                Expression::Index { .. } 
-                    => return Err(
-                        "Indexes are not iterable".to_string()),
-                Expression::Comprehension { .. } 
-                    => return Err(
-                        "Comprehensions are not iterable".to_string())
-            };
-
-            let Value::List{e: iterator_list} = v 
-                else { return Err("Invalid Type".to_string())};
-
-            for list_item in iterator_list {
-                enviornment.insert(params.loop_var.clone(), list_item);
-
-                eval_statements(enviornment, &params.statements, importing)?;
+            let v = eval_expression(enviornment, &params.iterate_expression, 
+                                    source)?;
+
+            for list_item in iterable_items(v)? {
+                enviornment.assign(params.loop_var.clone(), list_item)?;
+
+                let label = params.label.as_ref();
+                match eval_statements(&enviornment.block(), &params.statements, 
+                                      source)? {
+                    ControlFlow::Break(target) 
+                        if targets_loop(target.as_ref(), label) => break,
+                    ControlFlow::Continue(target) 
+                        if targets_loop(target.as_ref(), label) => continue,
+                    ControlFlow::Next => (),
+                    flow => return Ok(flow),
+                }
             }
         },
-        Statement::FunctionDefinition { name, arguments, 
-                                        statements, return_expression } => {
+        Statement::FunctionDefinition { name, arguments, statements, .. } => {
             if enviornment.get(name).is_some() {
-                return Err("Function '{}' is already defined!".to_string());
+                return Err("Function '{}' is already defined!".to_string()
+                               .into());
             }
+            check_parameters(name, arguments)?;
 
-            enviornment.insert(name.to_string(), 
+            // Definitions are bound the way plain assignment binds a name,
+            // so one made inside a block is still there after it
+            enviornment.assign(name.to_string(), 
                                Value::UserDefFunction { 
                                     name: name.to_string(),
                                     statements: statements.clone(),
                                     arguments: arguments.clone(),
-                                    return_expression: return_expression.clone()
-                                });
+                                    env: enviornment.clone(),
+                                })?;
+        },
+        Statement::StructDefinition { name, fields } => {
+            for (idx, field) in fields.iter().enumerate() {
+                if fields[..idx].contains(field) {
+                    return Err(format!("Field '{}' is declared more than once \
+                                        in struct '{}'", field, name).into());
+                }
+            }
+
+            enviornment.assign(name.to_string(), 
+                               Value::StructDef { 
+                                    name: name.to_string(), 
+                                    fields: fields.clone() 
+                                })?;
+        },
+        Statement::EnumDefinition { name, variants } => {
+            for (idx, variant) in variants.iter().enumerate() {
+                if variants[..idx].iter().any(|v| v.name == variant.name) {
+                    return Err(format!("Variant '{}' is declared more than \
+                                        once in enum '{}'", 
+                                       variant.name, name).into());
+                }
+            }
+
+            enviornment.assign(name.to_string(), 
+                               Value::EnumDef { 
+                                    name: name.to_string(), 
+                                    variants: variants.clone() 
+                                })?;
+        },
+        Statement::Match{params} => {
+            let (block, body) = select_arm(enviornment, params, source)?;
+            match body {
+                MatchBody::Expression { expression } => {
+                    eval_expression(&block, expression, source)?;
+                },
+                MatchBody::Block { statements } 
+                    => return eval_statements(&block, statements, source),
+            }
+        },
+        Statement::Return{expression, ..} => {
+            let v = match expression {
+                Some(expression) 
+                    => eval_expression(enviornment, expression, source)?,
+                None => Value::Null
+            };
+
+            return Ok(ControlFlow::Return(v));
+        },
+        Statement::Throw{expression, ..} => {
+            return match eval_expression(enviornment, expression, source)? {
+                Value::Error { error } => Err(*error),
+                value => Err(Error { value: Box::new(value.clone()), 
+                                     ..Error::from(value.to_string()) }),
+            };
+        },
+        Statement::Global{names} => {
+            for name in names {
+                enviornment.global(name.clone());
+            }
+        },
+        Statement::Nonlocal{names, ..} => {
+            for name in names {
+                enviornment.nonlocal(name.clone())?;
+            }
         },
+        Statement::Try{statements, catch, finally} => {
+            let mut result = eval_statements(&enviornment.block(), statements, 
+                                             source);
+            if let (Err(error), Some(catch)) = (&result, catch) {
+                let block = enviornment.block();
+                block.insert(catch.name.clone(), 
+                             Value::Error { error: Box::new(error.clone()) });
+                result = eval_statements(&block, &catch.statements, source);
+            }
+
+            // A finally block that returns, breaks or fails itself replaces
+            // whatever the rest of the statement did
+            if let Some(finally) = finally {
+                match eval_statements(&enviornment.block(), finally, 
+                                      source)? {
+                    ControlFlow::Next => (),
+                    flow => return Ok(flow),
+                }
+            }
+            return result;
+        },
+        Statement::Break{label} 
+            => return Ok(ControlFlow::Break(label.clone())),
+        Statement::Continue{label} 
+            => return Ok(ControlFlow::Continue(label.clone())),
         Statement::Import{path} => {    
             // Get the provided path to file 
             // and the directory the executable was called from
 
-            let args: Vec<String> = args().collect();
             let cwd = current_dir().unwrap();
             
             // The provided path
-            let origin_file: &String = &args[1];
+            let origin_file = source.path;
 
             // replace "." with the current working directory
-            let mut full_path = origin_file.clone();
+            let mut full_path = origin_file.to_string();
             if full_path.starts_with('.') {
                 full_path = origin_file.replacen('.', 
                                     cwd.to_str().unwrap(),
@@ -336,13 +491,14 @@ fn eval_statement(enviornment: &mut HashMap<String, Value>,
                         Ok(f) => f,
                         Err(_) => 
                             return Err(format!("Error opening file at {}", 
-                                            full_import_path))
+                                            full_import_path).into())
                     } 
                 } else if path.contains('/'){
                     match read_file(path) {
                         Ok(f) => f,
                         Err(_) => return 
-                            Err(format!("Error opening file at {}", path))
+                            Err(format!("Error opening file at {}", path)
+                                    .into())
                     } 
                 } else {
                     // Move one level up
@@ -376,123 +532,179 @@ fn eval_statement(enviornment: &mut HashMap<String, Value>,
                         }
                         if out.is_empty() {
                             return Err(format!("Error opening file at {}", 
-                                       path));
+                                       path).into());
                         }
                         out.to_string()
                     }
                 };
-            let ast = ProgramParser::new().parse(&external_code).unwrap();
+            let ast = parse(&external_code)
+                .map_err(|e| format!("{} in {}", e, path))?;
+
+            // Imported code is checked the same way as the file being run
+            let errors: Vec<String> 
+                = check_program(&ast, &enviornment.names(), source.strict)
+                    .iter()
+                    .map(|e| format!("{} at {} in {}", e.message, e.location, 
+                                     path))
+                    .collect();
+            if !errors.is_empty() {
+                return Err(errors.join("\n").into());
+            }
 
-            eval_program(enviornment, &ast, true)?;
+            eval_program(enviornment, &ast, 
+                         Source { importing: true, ..source })?;
         },
         //_ => return Err(format!("unhandled statement: {:?}", statement)),
     }
 
+    Ok(ControlFlow::Next)
+}
+
+fn check_parameters(name: &str, arguments: &[Parameter]) 
+    -> Result<(), Error> {
+    for (idx, parameter) in arguments.iter().enumerate() {
+        if arguments[..idx].iter().any(|p| p.name == parameter.name) {
+            return Err(format!("Parameter '{}' of '{}' is given more than \
+                                once", parameter.name, name).into());
+        }
+    }
     Ok(())
 }
 
-fn eval_statements(enviornment: &mut HashMap<String, Value>, 
+// Whether a break or continue aimed at `target` is handled by the loop
+// labeled `label`. An unlabeled break or continue targets the innermost loop
+fn targets_loop(target: Option<&String>, label: Option<&String>) -> bool {
+    target.is_none() || target == label
+}
+
+fn eval_statements(enviornment: &Environment, 
                    statements: &Vec<Statement>, 
-                   importing: bool) -> Result<(), String> {
+                   source: Source) -> Result<ControlFlow, Error> {
     
     for statement in statements {
-        eval_statement(enviornment, statement, importing)?;
+        let flow = eval_statement(enviornment, statement, source)
+            .map_err(|e| e.at(statement.location()))?;
+        if !matches!(flow, ControlFlow::Next) {
+            return Ok(flow);
+        }
     }
 
-    Ok(())
+    Ok(ControlFlow::Next)
 }
 
-fn eval_expression(enviornment: &mut HashMap<String, Value>, 
-    expression: &Expression, importing: bool) -> Result<Value, String>{
+fn eval_expression(enviornment: &Environment, 
+    expression: &Expression, source: Source) -> Result<Value, Error>{
     match expression {
         Expression::Int{v} => Ok(Value::Int{v: *v}),
         Expression::String{ s } => Ok(Value::Str{s: s.clone()}),
         Expression::Boolean{ b } => Ok(Value::Bool{b: *b}),
         Expression::Float{ f} => Ok(Value::Float{f: *f}),
         Expression::Character{ c } => Ok(Value::Char{c: *c}),
-        Expression::Identifier{name} => {
+        Expression::FString{ parts } => {
+            let mut s = String::new();
+            for part in parts {
+                match part {
+                    FStringPart::Text { s: text } => s.push_str(text),
+                    FStringPart::Value { expression, spec } => {
+                        let v = eval_expression(enviornment, expression, 
+                                                source)?;
+                        match spec {
+                            Some(spec) => s.push_str(&v.format(spec)?),
+                            None => s.push_str(&v.to_string()),
+                        }
+                    }
+                }
+            }
+            Ok(Value::Str{s})
+        },
+        Expression::Identifier { name, .. } => {
             match enviornment.get(name) {
-                Some(v) => Ok(v.clone()),
-                None => Err(format!("'{}' is not defined", &name))
+                Some(v) => Ok(v),
+                None => Err(Error::name_error(
+                    format!("'{}' is not defined", &name)))
             }
         },
-        Expression::Call{function, arguments} =>  {
-            let vals = eval_expressions(enviornment, arguments, importing)?;
-
-            let Some(v) = enviornment.get(function) 
-                else { return Err(format!("'{}' is not defined", &function)) };
-            
-            let mut local_env = enviornment.clone();
-
-            match v {
-                Value::Function{f, ..} => {
-                    if importing && (function == "print" || 
-                                     function == "println" ) {
-
-                            return Ok(Value::Null);     
-                    }
-                    f(vals)
-                },
-                Value::UserDefFunction {statements, 
-                                        arguments , return_expression, ..} => {
-                    if vals.len() != arguments.len() {
-                        return Err(format!("Expected {} arguments, got {}", 
-                                            arguments.len(), 
-                                            vals.len()))
-                    }
-                    for (value, name) in vals.iter().zip(arguments.iter()) {
-                        local_env.insert(name.to_string(), value.clone());
-                    }
-                    eval_statements(&mut local_env, statements, importing)?;
-                    
-                    match return_expression {
-                        Some(return_exp) => {
-                            match eval_expression(&mut enviornment.clone(),
-                                      return_exp, importing) {
-                                Ok(v) => Ok(v.clone()),
-                                Err(e) 
-                                    => Err(e)
-                            }
-                        },
-                        None => Ok(Value::Null)
-                    }
+        Expression::Call{function, arguments, location} =>  {
+            let function = eval_expression(enviornment, function, source)?;
+            let mut vals = vec![];
+            let mut named = vec![];
+            for argument in arguments {
+                let value = eval_expression(enviornment, &argument.expression, 
+                                            source)?;
+                match (&argument.name, value) {
+                    (Some(name), value) => named.push((name.clone(), value)),
+                    (None, Value::Map { m }) if argument.is_spread => {
+                        for (key, value) in m.borrow().clone() {
+                            let MapKey::Str { s: name } = key 
+                                else { return Err(Error::type_error(format!(
+                                    "Cannot spread the key '{}' into a named \
+                                     argument", Value::from(key)))) };
+                            named.push((name, value));
+                        }
+                    },
+                    (None, value @ (Value::List { .. } | Value::Range { .. })) 
+                        if argument.is_spread => vals.extend(value),
+                    (None, value) if argument.is_spread 
+                        => return Err(Error::type_error(
+                            format!("Cannot spread {} into \
+                                    arguments", value.type_name()))),
+                    (None, value) => vals.push(value),
+                }
+            }
 
-                },
-                _ => Err(format!("'{function}' is not a function"))
+            call(function, vals, named, source)
+                .map_err(|e| e.at(Some(*location)))
+        },
+        Expression::Operation { 
+            lhs, rhs, operator: operator @ (Operator::And | Operator::Or), 
+            location
+        } => {
+            // Only evaluate the rhs if the lhs does not decide the result
+            let lhs = eval_expression(enviornment, lhs, source)?;
+            let Value::Bool { b } = lhs
+                else { return Err(Error::type_error(
+                    format!("Operands of '{}' must be of type \
+                            'bool'", operator))) };
+
+            match (operator, b) {
+                (Operator::And, false) | (Operator::Or, true) 
+                    => Ok(Value::Bool { b }),
+                _ => {
+                    let rhs = eval_expression(enviornment, rhs, source)?;
+                    operate(operator, &lhs, &rhs)
+                        .map_err(|e| e.at(Some(*location)))
+                }
             }
         },
-        Expression::Operation { lhs, rhs, operator } => {
+        Expression::Operation { lhs, rhs, operator, location } => {
             let expressions = vec![lhs, rhs];
             let mut vals = vec![];
 
             for expression in expressions {
-                match eval_expression(enviornment, expression, importing) {
+                match eval_expression(enviornment, expression, source) {
                     Ok(v) => vals.push(v),
                     Err(e) => return Err(e),
                 }
             }
 
             if let [lhs, rhs] = vals.as_slice() {
-                let new_val = operate(operator, lhs, rhs)?;
+                let new_val = operate(operator, lhs, rhs)
+                    .map_err(|e| e.at(Some(*location)))?;
                 if new_val == Value::Null {
-                    return Err("Invalid Operation".to_string())
+                    return Err(Error::type_error(
+                        "Invalid Operation".to_string()))
                 }
                 Ok(new_val)
             }else{
-                Err("dev error: ".to_string())
+                Err("dev error: ".to_string().into())
             }
         },
         Expression::List { items} => {
             let mut vals: Vec<Value> = vec![];
             
             for item in items {
-                let v = 
-                    match eval_expression(enviornment, 
-                                          &item.expression, 
-                                          importing) {
-                        Ok(v) => v,
-                        Err(e) => return Err(e)
-                    };
+                let v = eval_expression(enviornment, &item.expression, 
+                                        source)?;
 
                 if !item.is_spread {
                     vals.push(v);
@@ -500,139 +712,785 @@ fn eval_expression(enviornment: &mut HashMap<String, Value>,
                 }
 
                 match v {
-                    Value::List{mut e} => vals.append(&mut e),
-                    _ => return Err("only lists can be spread!".to_string())
+                    Value::List{e} => vals.extend(e.borrow().iter().cloned()),
+                    Value::Range { .. } => vals.extend(v),
+                    _ => return Err(Error::type_error(
+                        "only lists can be spread!".to_string()))
                 }
             }
 
-            Ok(Value::List{e: vals})
+            Ok(Value::List{e: shared(vals)})
         },
-        Expression::Prefix { name, operator, rhs } => {
+        Expression::Prefix { name, operator, rhs, .. } => {
             let lhs = match enviornment.get(name) {
-                Some(v) => v.clone(),
-                None => return Err(format!("'{}' is not defined", name))
+                Some(v) => v,
+                None => return Err(Error::name_error(
+                    format!("'{}' is not defined", name)))
             };
 
-            let v = match eval_expression(enviornment, rhs, importing) {
-                Ok(v) => v,
-                Err(e) => return Err(e)
-            };
+            let v = eval_expression(enviornment, rhs, source)?;
 
             let new_val = operate(operator, &lhs, &v)?;
             if new_val == Value::Null {
-                return Err(format!("Cannot operate on {}", name))
+                return Err(Error::type_error(
+                    format!("Cannot operate on {}", name)))
             }
-            enviornment.insert(name.clone(), new_val.clone());
+            enviornment.assign(name.clone(), new_val.clone())?;
 
             Ok(new_val)
         },
-        Expression::Index { name, idx_exp } => {
-            let Some(var) = enviornment.get(name) 
-                else { return Err(format!("'{}' is not defined", name)) };
+        Expression::Map { entries } => {
+            let mut m = BTreeMap::new();
 
-            let exp_res = eval_expression(&mut enviornment.clone(), idx_exp, 
-                                                 importing)?;
+            for (key, value) in entries {
+                let key = eval_expression(enviornment, key, source)?;
+                let value = eval_expression(enviornment, value, source)?;
+                m.insert(MapKey::try_from(key)?, value);
+            }
 
-            let Value::Int { v: idx } = exp_res 
-                else { return Err("Index must be of type int".to_string()) };
+            Ok(Value::Map { m: shared(m) })
+        },
+        Expression::StructLiteral { name, fields } => {
+            let Some(Value::StructDef { fields: declared, .. }) 
+                = enviornment.get(name) 
+                else { return Err(Error::type_error(
+                    format!("'{}' is not a struct", name))) };
+
+            for (idx, (field, _)) in fields.iter().enumerate() {
+                if !declared.contains(field) {
+                    return Err(no_such_field(name, field));
+                }
+                if fields[..idx].iter().any(|(f, _)| f == field) {
+                    return Err(Error::type_error(
+                        format!("Field '{}' is given more than once", 
+                                field)));
+                }
+            }
 
-            let mut iterator = var.clone().into_iter();
-            let length = iterator.clone().count();
+            let mut values = vec![];
+            for field in declared {
+                let Some((_, exp)) = fields.iter().find(|(f, _)| *f == field)
+                    else { 
+                        return Err(Error::type_error(
+                            format!("Missing field '{}' in struct '{}'", 
+                                    field, name))) 
+                    };
+                values.push((field, eval_expression(enviornment, exp, 
+                                                    source)?));
+            }
 
-            if iterator.value == Value::Null {
-                return Err(format!("Cannot iterate over variable {}", name))
+            Ok(Value::Struct { name: name.clone(), fields: shared(values) })
+        },
+        Expression::Field { expression, field } => {
+            match eval_expression(enviornment, expression, source)? {
+                Value::EnumDef { name, variants } => {
+                    let Some(EnumVariant { fields, .. }) 
+                        = variants.iter().find(|v| v.name == *field)
+                        else { return Err(no_such_variant(&name, field)) };
+
+                    let variant = field.clone();
+                    if fields.is_empty() {
+                        Ok(Value::Enum { name, variant, values: vec![] })
+                    } else {
+                        Ok(Value::VariantConstructor { 
+                            name, variant, arity: fields.len() 
+                        })
+                    }
+                },
+                v => field_of(&v, field)
             }
+        },
+        Expression::Lambda { arguments, statements, .. } => {
+            check_parameters("lambda", arguments)?;
+            Ok(Value::UserDefFunction { 
+                name: "lambda".to_string(), 
+                statements: statements.clone(), 
+                arguments: arguments.clone(), 
+                env: enviornment.clone() 
+            })
+        },
+        Expression::Not { rhs } => {
+            match eval_expression(enviornment, rhs, source)? {
+                Value::Bool { b } => Ok(Value::Bool { b: !b }),
+                _ => Err(Error::type_error(
+                    "Operand of '!' must be of type 'bool'".to_string()))
+            }
+        },
+        Expression::Index { expression, idx_exp } => {
+            let var = eval_expression(enviornment, expression, source)?;
+            let idx = eval_expression(enviornment, idx_exp, source)?;
 
-            let usize_idx = idx.unsigned_abs() as usize;
+            index(&var, idx)
+        },
+        Expression::Slice { expression, start, end, step } => {
+            let var = eval_expression(enviornment, expression, source)?;
+            let bounds = eval_slice_bounds(enviornment, start, end, step, 
+                                           source)?;
 
-            if usize_idx > length {
-                return Err(format!("Index {} is out of bounds", idx))
+            slice(&var, &bounds)
+        },
+        Expression::Comprehension { iterate_exp, clauses } => {
+            let mut output = vec![];
+            comprehension(&enviornment.child(), clauses, source, 
+                          &mut |local_env| {
+                output.push(eval_expression(local_env, iterate_exp, 
+                                            source)?);
+                Ok(())
+            })?;
+            Ok(Value::List{e: shared(output)})
+        },
+        Expression::MapComprehension { key, value, clauses } => {
+            let mut output = BTreeMap::new();
+            comprehension(&enviornment.child(), clauses, source, 
+                          &mut |local_env| {
+                let key = eval_expression(local_env, key, source)?;
+                let value = eval_expression(local_env, value, source)?;
+                output.insert(MapKey::try_from(key)?, value);
+                Ok(())
+            })?;
+            Ok(Value::Map{m: shared(output)})
+        },
+        Expression::Match { params } => {
+            let (block, body) = select_arm(enviornment, params, source)?;
+            match body {
+                MatchBody::Expression { expression } 
+                    => eval_expression(&block, expression, source),
+                MatchBody::Block { statements } => {
+                    match eval_statements(&block, statements, source)? {
+                        ControlFlow::Next => Ok(Value::Null),
+                        _ => Err("Only a match statement can return, break \
+                                  or continue from its arms".to_string().into())
+                    }
+                }
             }
+        },
+        //_=> Err(format!("unhandled expression: {:?}", expression)),
+    }
+}
+
+fn no_such_field(struct_name: &str, field: &str) -> Error {
+    Error::field_error(format!("Struct '{}' has no field '{}'", 
+                               struct_name, field))
+}
+
+// Turns an index that may count from the end into a position in a sequence
+// of `len` items
+fn resolve_index(idx: i32, len: usize) -> Result<usize, Error> {
+    let position = if idx < 0 { len as i64 + i64::from(idx) } 
+                   else { i64::from(idx) };
+
+    if position < 0 || position >= len as i64 {
+        return Err(Error::index_error(
+            format!("Index {} is out of bounds", idx)));
+    }
+
+    Ok(position as usize)
+}
 
-            if idx < 0 {
-                return Ok(iterator.nth(length - usize_idx)
-                    .unwrap_or_else(|| panic!("Err retreiving value at {}", 
-                                               idx)))
+// One step from a value to a value held inside it, as in the `[i]`, `.x`
+// and `[a:b]` of `grid[i].x[a:b]`
+enum PathStep {
+    Index(Value),
+    Field(String),
+    Slice([Option<i64>; 3]),
+}
+
+// Splits an assignment target into the variable it starts from and the
+// steps into that variable. Indexes are evaluated once, from left to right
+fn place(enviornment: &Environment, target: &Expression, source: Source) 
+    -> Result<(String, Vec<PathStep>), Error> {
+    match target {
+        Expression::Identifier { name, .. } => Ok((name.clone(), vec![])),
+        Expression::Index { expression, idx_exp } => {
+            let (name, mut steps) = place(enviornment, expression, source)?;
+            let idx = eval_expression(enviornment, idx_exp, source)?;
+            steps.push(PathStep::Index(idx));
+            Ok((name, steps))
+        },
+        Expression::Field { expression, field } => {
+            let (name, mut steps) = place(enviornment, expression, source)?;
+            steps.push(PathStep::Field(field.clone()));
+            Ok((name, steps))
+        },
+        Expression::Slice { expression, start, end, step } => {
+            let (name, mut steps) = place(enviornment, expression, source)?;
+            let bounds = eval_slice_bounds(enviornment, start, end, step, 
+                                           source)?;
+            steps.push(PathStep::Slice(bounds));
+            Ok((name, steps))
+        },
+        _ => Err("Can only assign to variables and to the elements and \
+                  fields inside them".to_string().into())
+    }
+}
+
+fn get_step(container: &Value, step: &PathStep) -> Result<Value, Error> {
+    match step {
+        PathStep::Index(idx) => index(container, idx.clone()),
+        PathStep::Field(field) => field_of(container, field),
+        PathStep::Slice(bounds) => slice(container, bounds),
+    }
+}
+
+// Replaces the value at the end of the path. The containers along it are
+// changed in place, so every reference to them sees the new value
+fn set_path(mut container: Value, steps: &[PathStep], value: Value) 
+    -> Result<(), Error> {
+    let (last, steps) = steps.split_last()
+        .expect("elements and fields are at least one step in");
+
+    for step in steps {
+        container = get_step(&container, step)?;
+    }
+
+    match (container, last) {
+        (Value::List { e }, PathStep::Index(idx)) => {
+            let Value::Int { v: idx } = idx 
+                else { return Err(Error::type_error(
+                    "Index must be of type int".to_string())) };
+            let mut e = e.borrow_mut();
+            let idx = resolve_index(*idx, e.len())?;
+            e[idx] = value;
+            Ok(())
+        },
+        (Value::Map { m }, PathStep::Index(key)) => {
+            m.borrow_mut().insert(MapKey::try_from(key.clone())?, value);
+            Ok(())
+        },
+        (Value::Str { .. }, PathStep::Index(_)) 
+            => Err(Error::type_error(
+                "Cannot assign to String Index".to_string())),
+        (v, PathStep::Index(_)) 
+            => Err(Error::type_error(
+                format!("Cannot index {}", v.type_name()))),
+        (Value::Struct { name, fields }, PathStep::Field(field)) => {
+            let mut fields = fields.borrow_mut();
+            let Some(slot) = fields.iter_mut().find(|(f, _)| f == field) 
+                else { return Err(no_such_field(&name, field)) };
+            slot.1 = value;
+            Ok(())
+        },
+        (v, PathStep::Field(field)) 
+            => Err(Error::type_error(
+                format!("Cannot assign to field '{}' of {}", 
+                        field, v.type_name()))),
+        (Value::List { e }, PathStep::Slice(bounds)) => {
+            let Value::List { e: new_items } = value 
+                else { return Err(Error::type_error(
+                    "Can only assign a List to a slice".to_string())) };
+            // Copied first, since a list can be assigned to a slice of itself
+            let new_items = new_items.borrow().clone();
+            assign_slice(&mut e.borrow_mut(), bounds, new_items)
+        },
+        (v, PathStep::Slice(_)) 
+            => Err(Error::type_error(
+                format!("Cannot assign to a slice of {}", v.type_name()))),
+    }
+}
+
+fn index(var: &Value, idx: Value) -> Result<Value, Error> {
+    if let Value::Map { m } = var {
+        let key = MapKey::try_from(idx)?;
+        return match m.borrow().get(&key) {
+            Some(v) => Ok(v.clone()),
+            None => Err(Error::key_error(format!("Key '{}' is not in the map", 
+                                                 Value::from(key))))
+        }
+    }
+
+    let Value::Int { v: idx } = idx 
+        else { return Err(Error::type_error(
+            "Index must be of type int".to_string())) };
+
+    match var {
+        Value::List { e } => {
+            let e = e.borrow();
+            Ok(e[resolve_index(idx, e.len())?].clone())
+        },
+        Value::Str { s } => {
+            let chars: Vec<char> = s.chars().collect();
+            let idx = resolve_index(idx, chars.len())?;
+            Ok(Value::Char { c: chars[idx] })
+        },
+        Value::Range { start, step, .. } => {
+            let idx = resolve_index(idx, var.len().unwrap_or(0))?;
+            Ok(range_item(*start, *step, idx))
+        },
+        v => Err(Error::type_error(format!("Cannot index {}", v.type_name())))
+    }
+}
+
+fn field_of(var: &Value, field: &str) -> Result<Value, Error> {
+    match var {
+        Value::Struct { name, fields } => {
+            match fields.borrow().iter().find(|(f, _)| f == field) {
+                Some((_, v)) => Ok(v.clone()),
+                None => Err(no_such_field(name, field))
             }
+        },
+        Value::Error { error } => {
+            let (line, column) = match error.location {
+                Some(location) => (Value::Int { v: location.line as i32 }, 
+                                   Value::Int { v: location.column as i32 }),
+                None => (Value::Null, Value::Null)
+            };
+            match field {
+                "kind" => Ok(Value::Str { s: error.kind.clone() }),
+                "message" => Ok(Value::Str { s: error.message.clone() }),
+                "line" => Ok(line),
+                "column" => Ok(column),
+                "value" => Ok(*error.value.clone()),
+                _ => Err(Error::field_error(format!("Error has no field '{}'", 
+                                                    field)))
+            }
+        },
+        v => Err(Error::type_error(format!("Cannot access field '{}' of {}", 
+                                           field, v.type_name())))
+    }
+}
+
+fn eval_slice_bounds(enviornment: &Environment, 
+                     start: &Option<Box<Expression>>, 
+                     end: &Option<Box<Expression>>, 
+                     step: &Option<Box<Expression>>, 
+                     source: Source) -> Result<[Option<i64>; 3], Error> {
+    let mut bounds = [None; 3];
+    for (bound, exp) in bounds.iter_mut().zip([start, end, step]) {
+        let Some(exp) = exp else { continue };
+        match eval_expression(enviornment, exp, source)? {
+            Value::Int { v } => *bound = Some(i64::from(v)),
+            _ => return Err(Error::type_error(
+                "Slice bounds must be of type int".to_string()))
+        }
+    }
+
+    Ok(bounds)
+}
+
+// Resolves the bounds of `[start:end:step]` on a sequence of `len` items.
+// Like in Python, missing bounds cover the whole sequence in the direction
+// of the step, negative bounds count from the end and bounds past either end
+// are clamped
+fn slice_range([start, end, step]: &[Option<i64>; 3], len: usize) 
+    -> Result<(i64, i64, i64), Error> {
+    let len = len as i64;
+    let step = step.unwrap_or(1);
+    if step == 0 {
+        return Err(Error::value_error("Slice step cannot be zero".to_string()));
+    }
+
+    let resolve = |bound: Option<i64>, default: i64| match bound {
+        None => default,
+        Some(b) => {
+            let b = if b < 0 { b + len } else { b };
+            if step > 0 { b.clamp(0, len) } else { b.clamp(-1, len - 1) }
+        }
+    };
 
-            Ok(iterator.nth(usize_idx)
-                .unwrap_or_else(|| panic!("Err retreiving value at {}", idx)))
+    if step > 0 {
+        Ok((resolve(*start, 0), resolve(*end, len), step))
+    } else {
+        Ok((resolve(*start, len - 1), resolve(*end, -1), step))
+    }
+}
+
+fn slice_positions(start: i64, end: i64, step: i64) -> Vec<usize> {
+    let mut positions = vec![];
+    let mut idx = start;
+    while (step > 0 && idx < end) || (step < 0 && idx > end) {
+        positions.push(idx as usize);
+        idx += step;
+    }
+
+    positions
+}
+
+fn slice(var: &Value, bounds: &[Option<i64>; 3]) -> Result<Value, Error> {
+    match var {
+        Value::List { e } => {
+            let e = e.borrow();
+            let (start, end, step) = slice_range(bounds, e.len())?;
+            let e = slice_positions(start, end, step).into_iter()
+                .map(|idx| e[idx].clone())
+                .collect();
+            Ok(Value::List { e: shared(e) })
+        },
+        Value::Str { s } => {
+            let chars: Vec<char> = s.chars().collect();
+            let (start, end, step) = slice_range(bounds, chars.len())?;
+            let s = slice_positions(start, end, step).into_iter()
+                .map(|idx| chars[idx])
+                .collect();
+            Ok(Value::Str { s })
         },
-        Expression::Comprehension { iterate_exp, var, control_exp } => {
-            let mut local_env = enviornment.clone();
-            let control_val = eval_expression(&mut local_env, 
-                                                      control_exp, importing)?;
+        v => Err(Error::type_error(format!("Cannot slice {}", v.type_name())))
+    }
+}
 
-            match control_val {
-                Value::List { e } => {
-                    let mut output = vec![];
-                    for item in e {
-                        local_env.insert(var.to_string(), item);
-                        let iterate_exp_val = 
-                            eval_expression(&mut local_env, 
-                                             iterate_exp, importing)?;
-                        output.push(iterate_exp_val);
-                    }
-                    Ok(Value::List{e: output})
-                },
-                Value::Str { s } => {
-                    let mut output = vec![];
-                    for c in s.chars() {
-                        local_env.insert(var.to_string(), Value::Char {c});
-                        let iterate_exp_val = 
-                            eval_expression(&mut local_env, 
-                                             iterate_exp, importing)?;
-
-                        output.push(iterate_exp_val);
-                    }
-                    Ok(Value::List{e: output})
-                },
-                Value::Null 
-                    => Err("Null is not iterable".to_string()),
-                Value::Int { .. } 
-                    => Err("Int is not iterable".to_string()),
-                Value::Bool { .. } 
-                    => Err("Bool is not iterable".to_string()),
-                Value::Float { .. } 
-                    => Err("Float is not iterable".to_string()),
-                Value::Char { .. } 
-                    => Err("Char is not iterable".to_string()),
-                Value::Function { .. } 
-                    => Err("Function is not iterable".to_string()),
-                Value::UserDefFunction { .. } 
-                    => Err("Function is not iterable".to_string()),
+fn assign_slice(list: &mut Vec<Value>, bounds: &[Option<i64>; 3], 
+                mut new_items: Vec<Value>) -> Result<(), Error> {
+    let (start, end, step) = slice_range(bounds, list.len())?;
+    if step == 1 {
+        // A plain slice can be replaced by any number of items
+        let tail = list.split_off(end.max(start) as usize);
+        list.truncate(start as usize);
+        list.append(&mut new_items);
+        list.extend(tail);
+    } else {
+        let positions = slice_positions(start, end, step);
+        if positions.len() != new_items.len() {
+            return Err(Error::value_error(
+                format!("Cannot assign {} values to a slice of {} \
+                        items", new_items.len(), positions.len())));
+        }
+        for (idx, value) in positions.into_iter().zip(new_items) {
+            list[idx] = value;
+        }
+    }
+
+    Ok(())
+}
+
+fn no_such_variant(enum_name: &str, variant: &str) -> Error {
+    Error::field_error(format!("Enum '{}' has no variant '{}'", 
+                               enum_name, variant))
+}
+
+// Finds the first arm whose pattern fits the subject, and the block its body
+// runs in, where the names in that pattern are bound. The bindings of arms
+// that don't fit are thrown away
+fn select_arm<'a>(enviornment: &Environment, params: &'a MatchBlock, 
+                  source: Source) 
+                  -> Result<(Environment, &'a MatchBody), Error> {
+    let subject = eval_expression(enviornment, &params.subject, source)?;
+
+    for arm in &params.arms {
+        let mut bindings = vec![];
+        if match_pattern(enviornment, &arm.pattern, &subject, &mut bindings, 
+                         source)? {
+            let block = enviornment.block();
+            for (name, value) in bindings {
+                block.insert(name, value);
             }
+            return Ok((block, &arm.body));
         }
-        //_=> Err(format!("unhandled expression: {:?}", expression)),
     }
+
+    Err(Error::value_error(
+        format!("No match arm matches the value '{}'", subject)))
 }
 
-fn eval_expressions(enviornment: &mut HashMap<String, Value>, 
-                    expressions: &Vec<Expression>, 
-                    importing: bool) -> Result<Vec<Value>, String> {
-        let mut vals = vec![];
+fn match_pattern(enviornment: &Environment, pattern: &Expression, 
+                 value: &Value, bindings: &mut Vec<(String, Value)>, 
+                 source: Source) -> Result<bool, Error> {
+    match pattern {
+        Expression::Identifier { name, .. } => {
+            if name != "_" {
+                bindings.push((name.clone(), value.clone()));
+            }
+            Ok(true)
+        },
+        Expression::Int { .. } | Expression::String { .. } 
+        | Expression::Boolean { .. } | Expression::Float { .. } 
+        | Expression::Character { .. } 
+            => Ok(eval_expression(enviornment, pattern, source)? == *value),
+        Expression::List { items } => {
+            let Value::List { e } = value else { return Ok(false) };
+            match_list(enviornment, items, &e.borrow(), bindings, source)
+        },
+        Expression::Field { expression, field } 
+            => match_variant(enviornment, expression, field, &[], value, 
+                             bindings, source),
+        Expression::Call { function, arguments, .. } => {
+            let Expression::Field { expression, field } = function.as_ref() 
+                else { return Err("Invalid pattern in match arm".to_string()
+                                      .into()) };
+            match_variant(enviornment, expression, field, arguments, value, 
+                          bindings, source)
+        },
+        _ => Err("Invalid pattern in match arm".to_string().into())
+    }
+}
 
-        for expression in expressions {
-            match eval_expression(enviornment, expression, importing) {
-                Ok(v) => vals.push(v),
-                Err(e) => return Err(e),
+// Like assign_list, a packed last item collects whatever values are left
+fn match_list(enviornment: &Environment, items: &[ListItem], 
+              values: &[Value], bindings: &mut Vec<(String, Value)>, 
+              source: Source) -> Result<bool, Error> {
+    let (rest, fixed) = match items.split_last() {
+        Some((last, init)) if last.is_pack => (Some(last), init),
+        _ => (None, items),
+    };
+
+    if values.len() < fixed.len() 
+        || (rest.is_none() && values.len() != fixed.len()) {
+        return Ok(false);
+    }
+
+    for (item, value) in fixed.iter().zip(values) {
+        if item.is_spread {
+            return Err("Cannot use spread in a pattern".to_string().into());
+        }
+        if !match_pattern(enviornment, &item.expression, value, bindings, 
+                          source)? {
+            return Ok(false);
+        }
+    }
+
+    match rest {
+        Some(rest) => {
+            let rest_values = Value::List { 
+                e: shared(values[fixed.len()..].to_vec()) 
+            };
+            match_pattern(enviornment, &rest.expression, &rest_values, 
+                          bindings, source)
+        },
+        None => Ok(true)
+    }
+}
+
+fn match_variant(enviornment: &Environment, enum_exp: &Expression, 
+                 variant: &str, arguments: &[Argument], value: &Value, 
+                 bindings: &mut Vec<(String, Value)>, source: Source) 
+                 -> Result<bool, Error> {
+    let Expression::Identifier { name, .. } = enum_exp 
+        else { return Err("Invalid pattern in match arm".to_string().into()) };
+
+    let Some(Value::EnumDef { variants, .. }) = enviornment.get(name) 
+        else { return Err(Error::type_error(
+            format!("'{}' is not an enum", name))) };
+
+    let Some(declared) = variants.iter().find(|v| v.name == variant) 
+        else { return Err(no_such_variant(name, variant)) };
+
+    if declared.fields.len() != arguments.len() {
+        return Err(Error::type_error(
+            format!("Variant '{}.{}' holds {} values, the pattern \
+                    has {}", name, variant, declared.fields.len(), 
+                    arguments.len())));
+    }
+
+    let Value::Enum { name: value_enum, variant: value_variant, values } 
+        = value else { return Ok(false) };
+
+    if value_enum != name || value_variant != variant {
+        return Ok(false);
+    }
+
+    for (argument, value) in arguments.iter().zip(values) {
+        if argument.name.is_some() {
+            return Err("Invalid pattern in match arm".to_string().into());
+        }
+        if !match_pattern(enviornment, &argument.expression, value, 
+                          bindings, source)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+// The values a for loop or comprehension steps through. Strings yield their 
+// characters and maps yield their keys
+fn iterable_items(value: Value) -> Result<ValueIterator, Error> {
+    if !value.is_iterable() {
+        return Err(Error::type_error(
+            format!("{} is not iterable", value.type_name())));
+    }
+    Ok(value.into_iter())
+}
+
+// Runs the clauses of a comprehension left to right, so later generators are
+// nested inside earlier ones. `emit` is called with the loop variables bound
+// each time every filter passes
+fn comprehension(enviornment: &Environment, clauses: &[ComprehensionClause],
+                 source: Source, 
+                 emit: &mut dyn FnMut(&Environment) -> Result<(), Error>)
+    -> Result<(), Error> {
+    let Some((clause, rest)) = clauses.split_first() else {
+        return emit(enviornment);
+    };
+
+    match clause {
+        ComprehensionClause::For { target, iterable } => {
+            let items = eval_expression(enviornment, iterable, source)?;
+            for item in iterable_items(items)? {
+                assign(enviornment, target.clone(), item, Binding::Assign, 
+                       source)?;
+                comprehension(enviornment, rest, source, emit)?;
+            }
+        },
+        ComprehensionClause::If { condition } => {
+            match eval_expression(enviornment, condition, source)? {
+                Value::Bool { b: true } 
+                    => comprehension(enviornment, rest, source, emit)?,
+                Value::Bool { b: false } => (),
+                _ => return Err(Error::type_error(
+                    "Condition must be of type 'bool'".to_string())),
             }
+        },
+    }
+
+    Ok(())
+}
+
+fn call(function: Value, vals: Vec<Value>, named: Vec<(String, Value)>, 
+        source: Source) -> Result<Value, Error> {
+    if let (Some((argument, _)), false) 
+        = (named.first(), matches!(function, Value::UserDefFunction { .. })) {
+        return Err(Error::type_error(
+            format!("{} does not take named arguments like '{}'", 
+                    function, argument)));
+    }
+
+    match function {
+        Value::Function{name, f} => {
+            if source.importing && (name == "print" || name == "println" ) {
+                    return Ok(Value::Null);     
+            }
+            f(vals)
+        },
+        Value::UserDefFunction {name, statements, arguments, env} => {
+            // Calls run in a fresh scope on top of the one the function was 
+            // defined in, not the caller's
+            let local_env = env.child();
+            bind_arguments(&local_env, &name, &arguments, vals, named, 
+                           source)?;
+            match eval_statements(&local_env, &statements, source)? {
+                ControlFlow::Return(v) => Ok(v),
+                ControlFlow::Next => Ok(Value::Null),
+                flow => Err(flow.escaped().into()),
+            }
+        },
+        Value::VariantConstructor { name, variant, arity } => {
+            if vals.len() != arity {
+                return Err(Error::type_error(
+                    format!("Expected {} arguments, got {}", 
+                            arity, 
+                            vals.len())))
+            }
+            Ok(Value::Enum { name, variant, values: vals })
+        },
+        v => Err(Error::type_error(
+            format!("Cannot call a value of type {}", v.type_name())))
+    }
+}
+
+// Gives each parameter its value: the next positional argument, else the
+// argument named after it, else its default. Defaults are evaluated when
+// the call happens and can use the parameters before them
+fn bind_arguments(local_env: &Environment, function: &str, 
+                  parameters: &[Parameter], vals: Vec<Value>, 
+                  mut named: Vec<(String, Value)>, source: Source) 
+                  -> Result<(), Error> {
+    for (idx, (argument, _)) in named.iter().enumerate() {
+        if !parameters.iter().any(|p| &p.name == argument && !p.is_rest) {
+            return Err(Error::type_error(
+                format!("'{}' has no parameter named '{}'", 
+                        function, argument)));
+        }
+        if named[..idx].iter().any(|(other, _)| other == argument) {
+            return Err(Error::type_error(
+                format!("Argument '{}' of '{}' is given more than \
+                        once", argument, function)));
         }
+    }
+
+    let count = vals.len();
+    let mut vals = vals.into_iter();
+    for parameter in parameters {
+        if parameter.is_rest {
+            local_env.insert(parameter.name.clone(), 
+                             Value::List{e: shared(vals.by_ref().collect())});
+            continue;
+        }
+
+        let by_name = named.iter()
+            .position(|(argument, _)| argument == &parameter.name)
+            .map(|idx| named.remove(idx).1);
+        let value = match (vals.next(), by_name, &parameter.default) {
+            (Some(_), Some(_), _) 
+                => return Err(Error::type_error(
+                    format!("Argument '{}' of '{}' is given more \
+                            than once", parameter.name, function))),
+            (Some(value), None, _) | (None, Some(value), _) => value,
+            (None, None, Some(default)) 
+                => eval_expression(local_env, default, source)?,
+            (None, None, None) 
+                => return Err(Error::type_error(
+                    format!("Missing argument '{}' for '{}'", 
+                            parameter.name, function))),
+        };
+        local_env.insert(parameter.name.clone(), value);
+    }
 
-        Ok(vals)
+    if vals.next().is_some() {
+        let positional = parameters.iter().filter(|p| !p.is_rest).count();
+        return Err(Error::type_error(
+            format!("'{}' takes at most {} arguments, got {}", 
+                    function, positional, count)));
+    }
+
+    Ok(())
 }
 
 fn operate(operator: &Operator, lhs: &Value, rhs: &Value) 
-    -> Result<Value, String>{
+    -> Result<Value, Error>{
+        if matches!(operator, Operator::Divide | Operator::IntDivide 
+                              | Operator::Modulo) && rhs.is_zero() {
+            return Err(Error::zero_division(
+                format!("Division by zero in '{}'", operator)))
+        }
+
         match operator {
-            Operator::Plus => Ok(lhs + rhs),
-            Operator::Minus => Ok(lhs + rhs),
-            Operator::Times => Ok(lhs + rhs),
-            Operator::Divide => Ok(lhs + rhs),
-            Operator::LessThan => Ok(lhs + rhs),
-            Operator::GreaterThan => Ok(lhs + rhs),
+            Operator::Plus => overflow(operator, lhs, rhs, lhs + rhs),
+            Operator::Minus => overflow(operator, lhs, rhs, lhs - rhs),
+            Operator::Times => overflow(operator, lhs, rhs, lhs * rhs),
+            Operator::Divide => overflow(operator, lhs, rhs, lhs / rhs),
+            Operator::Modulo => overflow(operator, lhs, rhs, lhs % rhs),
+            Operator::Power => overflow(operator, lhs, rhs, lhs.pow(rhs)),
+            Operator::IntDivide 
+                => overflow(operator, lhs, rhs, lhs.floor_div(rhs)),
+            Operator::LessThan => compare(operator, lhs, rhs, Ordering::is_lt),
+            Operator::GreaterThan 
+                => compare(operator, lhs, rhs, Ordering::is_gt),
+            Operator::LessEqual 
+                => compare(operator, lhs, rhs, Ordering::is_le),
+            Operator::GreaterEqual 
+                => compare(operator, lhs, rhs, Ordering::is_ge),
             Operator::Equal => Ok(Value::Bool{b: lhs == rhs}),
             Operator::NotEqual => Ok(Value::Bool{b: lhs != rhs}),
+            Operator::In => Ok(Value::Bool{b: rhs.contains(lhs)?}),
+            Operator::And | Operator::Or => {
+                match (lhs, rhs) {
+                    (Value::Bool { b: l_b }, Value::Bool { b: r_b }) 
+                        => Ok(Value::Bool { 
+                            b: if matches!(operator, Operator::And) 
+                                { *l_b && *r_b } else { *l_b || *r_b } }),
+                    _ => Err(Error::type_error(
+                        format!("Operands of '{}' must be of type \
+                                'bool'", operator)))
+                }
+            },
         }
 }
-              
+
+// Operations on two Ints give Null when the result doesn't fit in an Int
+fn overflow(operator: &Operator, lhs: &Value, rhs: &Value, result: Value) 
+    -> Result<Value, Error> {
+    match (lhs, rhs, &result) {
+        (Value::Int { .. }, Value::Int { .. }, Value::Null) 
+            => Err(Error::new("OverflowError", 
+                              format!("Integer overflow in '{}'", operator))),
+        _ => Ok(result)
+    }
+}
+
+fn compare(operator: &Operator, lhs: &Value, rhs: &Value, 
+           test: fn(Ordering) -> bool) -> Result<Value, Error> {
+    match lhs.partial_cmp(rhs) {
+        Some(ordering) => Ok(Value::Bool{b: test(ordering)}),
+        None => Err(Error::type_error(
+            format!("Cannot compare {} and {} with '{}'", 
+                    lhs.type_name(), rhs.type_name(), operator)))
+    }
+}
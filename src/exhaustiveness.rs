@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use crate::ast::{ComprehensionClause, EnumVariant, Expression, FStringPart,
+                 MatchBlock, MatchBody, Parameter, Program, Statement};
+
+// Enum declarations and match blocks found anywhere in a program
+#[derive(Default)]
+struct Collector<'a> {
+    enums: HashMap<&'a str, &'a [EnumVariant]>,
+    matches: Vec<&'a MatchBlock>,
+}
+
+// Finds matches over an enum declared in the program that leave some of its
+// variants unhandled. These are only warnings, a value that no arm matches
+// is reported when the match runs
+pub fn match_warnings(Program::Body{statements}: &Program) -> Vec<String> {
+    let mut collector = Collector::default();
+    collector.statements(statements);
+
+    collector.matches.iter()
+        .filter_map(|params| missing_variants(params, &collector.enums))
+        .collect()
+}
+
+fn missing_variants(params: &MatchBlock,
+                    enums: &HashMap<&str, &[EnumVariant]>) -> Option<String> {
+    let mut enum_name = None;
+    let mut covered = vec![];
+
+    for arm in &params.arms {
+        let (name, variant, arguments) = match &arm.pattern {
+            Expression::Identifier { .. } => return None,
+            Expression::Field { expression, field }
+                => (expression.as_ref(), field, &[][..]),
+            Expression::Call { function, arguments, .. } => {
+                let Expression::Field { expression, field }
+                    = function.as_ref() else { continue };
+                (expression.as_ref(), field, arguments.as_slice())
+            },
+            _ => continue
+        };
+
+        let Expression::Identifier { name, .. } = name else { continue };
+        enum_name.get_or_insert(name);
+
+        // An arm only handles the whole variant if it binds every value
+        if arguments.iter()
+            .all(|a| matches!(a.expression, Expression::Identifier { .. })) {
+            covered.push(variant);
+        }
+    }
+
+    let enum_name = enum_name?;
+    let variants = enums.get(enum_name.as_str())?;
+
+    let missing: Vec<&str> = variants.iter()
+        .filter(|v| !covered.contains(&&v.name))
+        .map(|v| v.name.as_str())
+        .collect();
+
+    if missing.is_empty() {
+        return None;
+    }
+
+    Some(format!("Match on enum '{}' does not handle {}",
+                 enum_name, missing.join(", ")))
+}
+
+impl<'a> Collector<'a> {
+    fn statements(&mut self, statements: &'a [Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &'a Statement) {
+        match statement {
+            Statement::Import { .. }
+            | Statement::StructDefinition { .. }
+            | Statement::Break { .. }
+            | Statement::Continue { .. }
+            | Statement::Global { .. }
+            | Statement::Nonlocal { .. } => (),
+            Statement::Expression { expression }
+                => self.expression(expression),
+            Statement::Assignment { lhs, rhs, .. } 
+            | Statement::Declaration { lhs, rhs, .. } => {
+                self.expression(lhs);
+                self.expression(rhs);
+            },
+            Statement::OperatorAssignment { lhs, rhs, .. } => {
+                self.expression(lhs);
+                self.expression(rhs);
+            },
+            Statement::If { params } => {
+                self.expression(&params.condition);
+                self.statements(&params.statements);
+                let (conditions, branches) = &params.elif_data;
+                for (condition, statements) in conditions.iter().zip(branches) {
+                    self.expression(condition);
+                    self.statements(statements);
+                }
+                if let Some(else_statements) = &params.else_statements {
+                    self.statements(else_statements);
+                }
+            },
+            Statement::While { condition, statements, .. } => {
+                self.expression(condition);
+                self.statements(statements);
+            },
+            Statement::For { params } => {
+                self.expression(&params.iterate_expression);
+                self.statements(&params.statements);
+            },
+            Statement::FunctionDefinition { arguments, statements, .. } => {
+                self.parameters(arguments);
+                self.statements(statements);
+            },
+            Statement::EnumDefinition { name, variants } => {
+                self.enums.insert(name, variants);
+            },
+            Statement::Match { params } => self.match_block(params),
+            Statement::Return { expression, .. } => {
+                if let Some(expression) = expression {
+                    self.expression(expression);
+                }
+            },
+            Statement::Throw { expression, .. } => self.expression(expression),
+            Statement::Try { statements, catch, finally } => {
+                self.statements(statements);
+                if let Some(catch) = catch {
+                    self.statements(&catch.statements);
+                }
+                if let Some(finally) = finally {
+                    self.statements(finally);
+                }
+            },
+        }
+    }
+
+    fn expression(&mut self, expression: &'a Expression) {
+        match expression {
+            Expression::Int { .. }
+            | Expression::String { .. }
+            | Expression::Boolean { .. }
+            | Expression::Float { .. }
+            | Expression::Character { .. }
+            | Expression::Identifier { .. } => (),
+            Expression::FString { parts } => {
+                for part in parts {
+                    if let FStringPart::Value { expression, .. } = part {
+                        self.expression(expression);
+                    }
+                }
+            },
+            Expression::List { items } => {
+                for item in items {
+                    self.expression(&item.expression);
+                }
+            },
+            Expression::Map { entries } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            },
+            Expression::Call { function, arguments, .. } => {
+                self.expression(function);
+                for argument in arguments {
+                    self.expression(&argument.expression);
+                }
+            },
+            Expression::Operation { lhs, rhs, .. } => {
+                self.expression(lhs);
+                self.expression(rhs);
+            },
+            Expression::Prefix { rhs, .. } | Expression::Not { rhs }
+                => self.expression(rhs),
+            Expression::Index { expression, idx_exp } => {
+                self.expression(expression);
+                self.expression(idx_exp);
+            },
+            Expression::Slice { expression, start, end, step } => {
+                self.expression(expression);
+                for bound in [start, end, step].into_iter().flatten() {
+                    self.expression(bound);
+                }
+            },
+            Expression::StructLiteral { fields, .. } => {
+                for (_, value) in fields {
+                    self.expression(value);
+                }
+            },
+            Expression::Field { expression, .. }
+                => self.expression(expression),
+            Expression::Lambda { arguments, statements, .. } => {
+                self.parameters(arguments);
+                self.statements(statements);
+            },
+            Expression::Comprehension { iterate_exp, clauses } => {
+                self.expression(iterate_exp);
+                self.clauses(clauses);
+            },
+            Expression::MapComprehension { key, value, clauses } => {
+                self.expression(key);
+                self.expression(value);
+                self.clauses(clauses);
+            },
+            Expression::Match { params } => self.match_block(params),
+        }
+    }
+
+    fn parameters(&mut self, parameters: &'a [Parameter]) {
+        for default in parameters.iter().filter_map(|p| p.default.as_ref()) {
+            self.expression(default);
+        }
+    }
+
+    fn clauses(&mut self, clauses: &'a [ComprehensionClause]) {
+        for clause in clauses {
+            match clause {
+                ComprehensionClause::For { target, iterable } => {
+                    self.expression(target);
+                    self.expression(iterable);
+                },
+                ComprehensionClause::If { condition }
+                    => self.expression(condition),
+            }
+        }
+    }
+
+    fn match_block(&mut self, params: &'a MatchBlock) {
+        self.matches.push(params);
+        self.expression(&params.subject);
+        for arm in &params.arms {
+            match &arm.body {
+                MatchBody::Expression { expression }
+                    => self.expression(expression),
+                MatchBody::Block { statements } => self.statements(statements),
+            }
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use lalrpop_util::lexer::Token;
+use lalrpop_util::ParseError;
+
+use crate::ast::{Align, FStringPart, FormatSpec, Lines};
+use crate::parser::ExpressionParser;
+
+// A literal that could not be turned into a value. The location is a byte
+// offset into the source file
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiteralError {
+    pub location: usize,
+    pub message: String,
+}
+
+// Replaces the escape sequences in the text between a literal's quotes.
+// `start` is where that text begins in the source file
+pub fn unescape(body: &str, start: usize) -> Result<String, LiteralError> {
+    let mut text = String::new();
+    let mut chars = body.char_indices();
+
+    while let Some((idx, c)) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+
+        let escaped = match chars.next() {
+            Some((_, 'n')) => Some('\n'),
+            Some((_, 't')) => Some('\t'),
+            Some((_, 'r')) => Some('\r'),
+            Some((_, '0')) => Some('\0'),
+            Some((_, '\\')) => Some('\\'),
+            Some((_, '"')) => Some('"'),
+            Some((_, '\'')) => Some('\''),
+            Some((_, 'u')) => unicode_escape(&mut chars),
+            _ => None
+        };
+
+        match escaped {
+            Some(c) => text.push(c),
+            None => return Err(LiteralError {
+                location: start + idx,
+                message: format!("Invalid escape sequence '{}'",
+                                 &body[idx..chars.offset()]),
+            }),
+        }
+    }
+
+    Ok(text)
+}
+
+// Reads the `{1F600}` part of a `\u{1F600}` escape
+fn unicode_escape(chars: &mut std::str::CharIndices) -> Option<char> {
+    if chars.next()?.1 != '{' {
+        return None;
+    }
+
+    let mut digits = String::new();
+    loop {
+        match chars.next()?.1 {
+            '}' => break,
+            c => digits.push(c),
+        }
+    }
+
+    if digits.is_empty() || digits.len() > 6 {
+        return None;
+    }
+
+    char::from_u32(u32::from_str_radix(&digits, 16).ok()?)
+}
+
+// Splits the text between the quotes of an f-string into plain text and the
+// expressions written in braces. `{{` and `}}` stand for literal braces.
+// Expressions can't contain `"` since it would end the f-string
+pub fn interpolate(lines: &Lines, body: &str, start: usize)
+    -> Result<Vec<FStringPart>, LiteralError> {
+    let chars: Vec<(usize, char)> = body.char_indices().collect();
+    let mut parts = vec![];
+    let mut text = String::new();
+    let mut segment_start = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (idx, c) = chars[i];
+        let next = chars.get(i + 1).map(|(_, c)| *c);
+
+        match (c, next) {
+            ('\\', Some('u')) => {
+                // Step over the braces of a \u{...} escape
+                while i < chars.len() && chars[i].1 != '}' {
+                    i += 1;
+                }
+            },
+            ('\\', _) => i += 1,
+            ('{', Some('{')) | ('}', Some('}')) => {
+                text.push_str(&unescape(&body[segment_start..idx],
+                                        start + segment_start)?);
+                text.push(c);
+                i += 1;
+                segment_start = idx + 2;
+            },
+            ('{', _) => {
+                text.push_str(&unescape(&body[segment_start..idx],
+                                        start + segment_start)?);
+                if !text.is_empty() {
+                    parts.push(FStringPart::Text { s: text });
+                    text = String::new();
+                }
+
+                let (end, part) = replacement_field(lines, body, start, idx)?;
+                parts.push(part);
+                segment_start = end + 1;
+                while i + 1 < chars.len() && chars[i + 1].0 <= end {
+                    i += 1;
+                }
+            },
+            ('}', _) => return Err(LiteralError {
+                location: start + idx,
+                message: "Unmatched '}' in f-string".to_string(),
+            }),
+            _ => (),
+        }
+        i += 1;
+    }
+
+    text.push_str(&unescape(&body[segment_start..], start + segment_start)?);
+    if !text.is_empty() {
+        parts.push(FStringPart::Text { s: text });
+    }
+
+    Ok(parts)
+}
+
+// Parses the `{expression:spec}` that opens at byte `open` of the body and
+// returns it along with where its closing brace is
+fn replacement_field(lines: &Lines, body: &str, start: usize, 
+                     open: usize)
+    -> Result<(usize, FStringPart), LiteralError> {
+    let mut depth = 0;
+    let mut colon = None;
+    let mut in_char = false;
+    let mut close = None;
+
+    let mut chars = body[open + 1..].char_indices();
+    while let Some((offset, c)) = chars.next() {
+        let idx = open + 1 + offset;
+        match c {
+            '\\' if in_char => { chars.next(); },
+            '\'' => in_char = !in_char,
+            _ if in_char => (),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth > 0 => depth -= 1,
+            '}' => {
+                close = Some(idx);
+                break;
+            },
+            ':' if depth == 0 && colon.is_none() => colon = Some(idx),
+            _ => (),
+        }
+    }
+
+    let Some(close) = close else {
+        return Err(LiteralError {
+            location: start + open,
+            message: "Unclosed '{' in f-string".to_string(),
+        })
+    };
+
+    let source = &body[open + 1..colon.unwrap_or(close)];
+    if source.trim().is_empty() {
+        return Err(LiteralError {
+            location: start + open,
+            message: "Empty expression in f-string".to_string(),
+        });
+    }
+
+    // Parsing the expression behind as much whitespace as comes before it in
+    // the file keeps the locations in its AST and errors true to the file
+    let padded = format!("{}{}", " ".repeat(start + open + 1), source);
+    let expression = ExpressionParser::new().parse(lines, &padded)
+        .map_err(expression_error)?;
+
+    let spec = match colon {
+        Some(colon) => Some(format_spec(&body[colon + 1..close],
+                                        start + colon + 1)?),
+        None => None
+    };
+
+    Ok((close, FStringPart::Value { expression, spec }))
+}
+
+fn expression_error(e: ParseError<usize, Token, LiteralError>) 
+    -> LiteralError {
+    let (location, message) = match e {
+        ParseError::InvalidToken { location }
+            => (location, "Invalid token".to_string()),
+        ParseError::UnrecognizedEof { location, .. }
+            => (location, "Unexpected end of f-string expression".to_string()),
+        ParseError::UnrecognizedToken { token: (location, token, _), .. }
+        | ParseError::ExtraToken { token: (location, token, _) }
+            => (location, format!("Unexpected '{}'", token)),
+        ParseError::User { error } => return error,
+    };
+
+    LiteralError { location, message }
+}
+
+fn format_spec(spec: &str, start: usize) -> Result<FormatSpec, LiteralError> {
+    let invalid = || LiteralError {
+        location: start,
+        message: format!("Invalid format spec '{}'", spec),
+    };
+    let align = |c| match c {
+        '<' => Some(Align::Left),
+        '>' => Some(Align::Right),
+        '^' => Some(Align::Center),
+        _ => None
+    };
+
+    let mut chars: Vec<char> = spec.chars().collect();
+    let mut fill = ' ';
+    let mut alignment = None;
+    if chars.len() >= 2 && align(chars[1]).is_some() {
+        fill = chars[0];
+        alignment = align(chars[1]);
+        chars.drain(..2);
+    } else if !chars.is_empty() && align(chars[0]).is_some() {
+        alignment = align(chars[0]);
+        chars.remove(0);
+    }
+
+    let rest: String = chars.into_iter().collect();
+    let zero = rest.starts_with('0');
+    let (width, precision) = match rest.split_once('.') {
+        Some((width, precision)) => (width, Some(precision)),
+        None => (rest.as_str(), None)
+    };
+
+    let width = match width {
+        "" => 0,
+        width => width.parse().map_err(|_| invalid())?,
+    };
+    let precision = match precision {
+        Some(precision) => Some(precision.parse().map_err(|_| invalid())?),
+        None => None
+    };
+
+    Ok(FormatSpec { fill, align: alignment, zero, width, precision })
+}
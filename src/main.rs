@@ -1,34 +1,47 @@
 use std::env::args;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Error};
-use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader};
 
 mod ast; 
+mod environment;
+mod error;
 mod eval;
+mod exhaustiveness;
+mod literal;
 mod constants;
+mod typecheck;
 mod value;
 
-use crate::value::Value;
+use crate::ast::{Lines, Program};
+use crate::environment::Environment;
+use crate::error::Error;
+use crate::value::{shared, MapKey, Value};
 
 #[macro_use]
 extern crate lalrpop_util; 
 
-lalrpop_mod!(pub parser);
+use lalrpop_util::ParseError;
+
+lalrpop_mod!(#[allow(clippy::all)] pub parser);
 
 
 fn main() {
     let args: Vec<String> = args().collect();
-    if args.len() != 2 {
-        println!("Usage: {} <filename>", args[0]);
-        return;
-    }
+    let (strict, path) = match args.as_slice() {
+        [_, path] => (false, path),
+        [_, flag, path] if flag == "--strict" => (true, path),
+        _ => {
+            println!("Usage: {} [--strict] <filename>", args[0]);
+            return;
+        }
+    };
 
-    let file = match read_file(&args[1]) {
+    let file = match read_file(path) {
         Ok(file) => file,
         Err(e) => panic!("{}", e)
     };
 
-    let mut enviornment = HashMap::new();
+    let enviornment = Environment::new();
     enviornment.insert("println".to_string(), 
         Value::Function{name: "println".to_string(), f: println_});
     
@@ -40,31 +53,85 @@ fn main() {
 
     enviornment.insert("range_step".to_string(), 
         Value::Function{name: "range_step".to_string(), f: range_step});
+
+    enviornment.insert("len".to_string(), 
+        Value::Function{name: "len".to_string(), f: len});
+
+    enviornment.insert("list".to_string(), 
+        Value::Function{name: "list".to_string(), f: list});
+
+    enviornment.insert("keys".to_string(), 
+        Value::Function{name: "keys".to_string(), f: keys});
+
+    enviornment.insert("values".to_string(), 
+        Value::Function{name: "values".to_string(), f: values});
+
+    enviornment.insert("items".to_string(), 
+        Value::Function{name: "items".to_string(), f: items});
+
+    enviornment.insert("has".to_string(), 
+        Value::Function{name: "has".to_string(), f: has});
+
+    enviornment.insert("remove".to_string(), 
+        Value::Function{name: "remove".to_string(), f: remove});
+
+    enviornment.insert("error".to_string(), 
+        Value::Function{name: "error".to_string(), f: error});
+
+    enviornment.insert("copy".to_string(), 
+        Value::Function{name: "copy".to_string(), f: copy});
         
-    let ast = parser::ProgramParser::new().parse(&file).unwrap();
+    let ast = match parse(&file) {
+        Ok(ast) => ast,
+        Err(e) => {
+            println!("\nError: {e}\n");
+            return;
+        }
+    };
+
+    let type_errors = typecheck::check_program(&ast, &enviornment.names(), 
+                                               strict);
+    if !type_errors.is_empty() {
+        println!();
+        for error in type_errors {
+            println!("Error: {} at {}", error.message, error.location);
+        }
+        println!();
+        return;
+    }
+
+    for warning in exhaustiveness::match_warnings(&ast) {
+        eprintln!("Warning: {}", warning);
+    }
     
-    let result = eval::eval_program(&mut enviornment, &ast, false);
+    let source = eval::Source { path, importing: false, strict };
+    let result = eval::eval_program(&enviornment, &ast, source);
 
     match &result {
         Ok(()) => (),
-        Err(s) => {
-            println!("\nError: {s}\n\nAST:\n{:?}\n", ast);
+        Err(e) => {
+            match e.location {
+                Some(location) => println!("\nError: {}\n{} at {}", 
+                                           e.message, e.kind, location),
+                None => println!("\nError: {}\n{}", e.message, e.kind),
+            }
+            println!("\nAST:\n{:?}\n", ast);
         },
     }
 }
 
-pub fn read_file(path: &str) -> Result<String, Error> {
-    let f = match File::open(path) {
-        Ok(f) => f,
-        Err(e) => return Err(e)
-    };
+pub fn read_file(path: &str) -> Result<String, io::Error> {
+    let f = File::open(path)?;
 
     let lines = BufReader::new(f).lines();
     let mut file = String::new();
 
     for s in lines{
         match s {
-            Ok(s) => file.push_str(&s),
+            Ok(s) => {
+                file.push_str(&s);
+                file.push('\n');
+            },
             Err(e) => return Err(e)
         }
     }
@@ -72,50 +139,166 @@ pub fn read_file(path: &str) -> Result<String, Error> {
     Ok(file)
 }
 
-
+// Parses a source file, describing any syntax error by where it happened
+pub fn parse(source: &str) -> Result<Program, String> {
+    let lines = Lines::new(source);
+    let position = |offset| lines.location(offset);
+    parser::ProgramParser::new().parse(&lines, source).map_err(|e| match e {
+        ParseError::InvalidToken { location } 
+            => format!("Invalid token at {}", position(location)),
+        ParseError::UnrecognizedEof { location, .. } 
+            => format!("Unexpected end of file at {}", 
+                       position(location)),
+        ParseError::UnrecognizedToken { token: (location, token, _), .. }
+        | ParseError::ExtraToken { token: (location, token, _) } 
+            => format!("Unexpected '{}' at {}", token, 
+                       position(location)),
+        ParseError::User { error } 
+            => format!("{} at {}", error.message, 
+                       position(error.location)),
+    })
+}
 
 #[allow(clippy::unnecessary_wraps)]
-fn println_(args: Vec<Value>) -> Result<Value, String> {
+fn println_(args: Vec<Value>) -> Result<Value, Error> {
     for arg in args {
         println!("{}", arg);
     }
     Ok(Value::Null)
 }
 
-fn print_(args: Vec<Value>) -> Result<Value, String> {
+fn print_(args: Vec<Value>) -> Result<Value, Error> {
     for arg in args {
         print!("{}", arg);
     }
     Ok(Value::Null)
 }
 
-fn range_step(args: Vec<Value>) -> Result<Value, String> {
-    let mut vals = vec![];
+// A negative step counts down from start to just above end
+fn range_step(args: Vec<Value>) -> Result<Value, Error> {
+    let [Value::Int{v: start}, Value::Int{v: end}, Value::Int{v: step}] 
+        = args.as_slice()
+        else { return Err(Error::type_error(
+            "range_step expects three Ints".to_string())) };
 
-    let start = args[0].clone();
-    let end = args[1].clone();
-    let step = args[2].clone();
+    if *step == 0 {
+        return Err(Error::value_error(
+            "range_step cannot have a step of zero".to_string()));
+    }
 
-    let Value::Int{v: s} = start 
-        else { return Err("Invalid Type".to_string())};
-    
-    let Value::Int{v: e} = end 
-        else { return Err("Invalid Type".to_string())};
+    Ok(Value::Range{start: *start, end: *end, step: *step})
+}
 
-    let Value::Int{v: st} = step 
-        else { return Err("Invalid Type".to_string())};
+fn range(args: Vec<Value>) -> Result<Value, Error> {
+    let [Value::Int{v: start}, Value::Int{v: end}] = args.as_slice()
+        else { return Err(Error::type_error(
+            "range expects two Ints".to_string())) };
 
-    for x in (s..e).step_by(st.try_into().unwrap()) {
-        vals.push(Value::Int{v: x});
+    Ok(Value::Range{start: *start, end: *end, step: 1})
+}
+
+fn len(args: Vec<Value>) -> Result<Value, Error> {
+    let [value] = args.as_slice()
+        else { return Err(Error::type_error(
+            "len expects a single value".to_string())) };
+
+    match value.len() {
+        Some(len) => Ok(Value::Int{v: i32::try_from(len)
+            .map_err(|_| Error::value_error(
+                "len is too large for an Int".to_string()))?}),
+        None => Err(Error::type_error(
+            format!("{} has no length", value.type_name())))
+    }
+}
+
+// Collects everything a for loop over the value would step through
+fn list(args: Vec<Value>) -> Result<Value, Error> {
+    let [value] = args.as_slice()
+        else { return Err(Error::type_error(
+            "list expects a single value".to_string())) };
+
+    if !value.is_iterable() {
+        return Err(Error::type_error(
+            format!("{} is not iterable", value.type_name())));
+    }
+
+    Ok(Value::List{e: shared(value.clone().into_iter().collect())})
+}
+
+fn keys(args: Vec<Value>) -> Result<Value, Error> {
+    let [Value::Map{m}] = args.as_slice()
+        else { return Err(Error::type_error(
+            "keys expects a single Map".to_string())) };
+
+    let keys = m.borrow().keys().cloned().map(Value::from).collect();
+    Ok(Value::List{e: shared(keys)})
+}
+
+fn values(args: Vec<Value>) -> Result<Value, Error> {
+    let [Value::Map{m}] = args.as_slice()
+        else { return Err(Error::type_error(
+            "values expects a single Map".to_string())) };
+
+    Ok(Value::List{e: shared(m.borrow().values().cloned().collect())})
+}
+
+fn items(args: Vec<Value>) -> Result<Value, Error> {
+    let [Value::Map{m}] = args.as_slice()
+        else { return Err(Error::type_error(
+            "items expects a single Map".to_string())) };
+
+    let pairs = m.borrow().iter()
+        .map(|(k, v)| Value::List{
+            e: shared(vec![Value::from(k.clone()), v.clone()])
+        })
+        .collect();
+
+    Ok(Value::List{e: shared(pairs)})
+}
+
+fn has(args: Vec<Value>) -> Result<Value, Error> {
+    let [Value::Map{m}, key] = args.as_slice()
+        else { return Err(Error::type_error(
+            "has expects a Map and a key".to_string())) };
+
+    let key = MapKey::try_from(key.clone())?;
+    Ok(Value::Bool{b: m.borrow().contains_key(&key)})
+}
+
+// Removes the key from the map in place. The map is returned as well, so
+// the call can be assigned or passed along
+fn remove(args: Vec<Value>) -> Result<Value, Error> {
+    let [map @ Value::Map{m}, key] = args.as_slice()
+        else { return Err(Error::type_error(
+            "remove expects a Map and a key".to_string())) };
+
+    let key = MapKey::try_from(key.clone())?;
+    if m.borrow_mut().remove(&key).is_none() {
+        return Err(Error::key_error(
+            format!("Key '{}' is not in the map", Value::from(key))))
     }
 
-    Ok(Value::List{e: vals})
+    Ok(map.clone())
 }
 
-fn range(args: Vec<Value>) -> Result<Value, String> {
-    let mut vals = args;
-    vals.push(Value::Int{v: 1});
-    range_step(vals)
+// A copy of the value that shares no lists, maps or structs with it
+fn copy(args: Vec<Value>) -> Result<Value, Error> {
+    let [value] = args.as_slice()
+        else { return Err(Error::type_error(
+            "copy expects a single value".to_string())) };
+
+    Ok(value.deep_copy())
 }
 
+// Makes an error value for `throw`, with the kind "Error" unless one is given
+fn error(args: Vec<Value>) -> Result<Value, Error> {
+    let (message, kind) = match args.as_slice() {
+        [Value::Str{s: message}] => (message, "Error"),
+        [Value::Str{s: message}, Value::Str{s: kind}] 
+            => (message, kind.as_str()),
+        _ => return Err(Error::type_error(
+            "error expects a message and optionally a kind".to_string())),
+    };
 
+    Ok(Value::Error{error: Box::new(Error::new(kind, message.clone()))})
+}
@@ -0,0 +1,882 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Argument, ComprehensionClause, Expression, FStringPart,
+                 Location, MatchBlock, MatchBody, Operator, Parameter,
+                 Program, Statement, Type};
+
+// A mistake found before the program runs
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeError {
+    pub location: Location,
+    pub message: String,
+}
+
+// What a call to a function defined in the program can be checked against
+#[derive(Clone)]
+struct Signature {
+    name: String,
+    parameters: Vec<Parameter>,
+    returns: Option<Type>,
+}
+
+// Mirrors a scope of the enviornment the program will run in. Names given
+// a value without an annotation have type any
+#[derive(Default)]
+struct Scope {
+    names: HashMap<String, Type>,
+    signatures: HashMap<String, Signature>,
+    constants: HashSet<String>,
+    // Names made global or nonlocal, with the index of the scope they
+    // belong to
+    outer: HashMap<String, usize>,
+    is_block: bool,
+}
+
+struct Checker {
+    strict: bool,
+    scopes: Vec<Scope>,
+    // The name and return type of each function being checked, innermost
+    // last
+    functions: Vec<(String, Option<Type>)>,
+    // The names bound anywhere in the program and in each function around
+    // the code being checked, innermost last
+    bodies: Vec<HashSet<String>>,
+    // Set once an import is found, since it can bind any name
+    imported: bool,
+    errors: Vec<TypeError>,
+}
+
+// Checks the annotations in a program before it runs. Values whose type
+// isn't known until then are let through. With `strict` every variable,
+// parameter and return type has to be annotated, except for names bound by
+// loops, destructuring and match patterns, which have nowhere to put one.
+// Variables also have to be declared before they are assigned to or read.
+// `builtins` are the names the program starts out with
+pub fn check_program(Program::Body{statements}: &Program, 
+                     builtins: &[String], strict: bool) -> Vec<TypeError> {
+    let mut names = HashSet::new();
+    let imported = !bound_names(statements, false, &mut names);
+    let mut checker = Checker {
+        strict,
+        scopes: vec![Scope::default()],
+        functions: vec![],
+        bodies: vec![names],
+        imported,
+        errors: vec![],
+    };
+    for name in builtins {
+        checker.declare(name, Type::Any);
+    }
+    checker.statements(statements);
+
+    checker.errors
+}
+
+// Collects the names a body can bind in its own scope, looking inside its
+// blocks. Functions defined in it only add their name and the names they
+// make global. Returns false if it imports a file, which could bind anything
+fn bound_names(statements: &[Statement], nested: bool,
+               names: &mut HashSet<String>) -> bool {
+    let mut known = true;
+    for statement in statements {
+        match statement {
+            Statement::Import { .. } => known = false,
+            Statement::Global { names: global } 
+                => names.extend(global.iter().cloned()),
+            Statement::Assignment { lhs, .. }
+            | Statement::Declaration { lhs, .. } if !nested 
+                => pattern_names(lhs, names),
+            Statement::StructDefinition { name, .. }
+            | Statement::EnumDefinition { name, .. } if !nested => {
+                names.insert(name.clone());
+            },
+            Statement::FunctionDefinition { name, statements, .. } => {
+                if !nested {
+                    names.insert(name.clone());
+                }
+                known &= bound_names(statements, true, names);
+            },
+            Statement::If { params } => {
+                known &= bound_names(&params.statements, nested, names);
+                for statements in &params.elif_data.1 {
+                    known &= bound_names(statements, nested, names);
+                }
+                if let Some(statements) = &params.else_statements {
+                    known &= bound_names(statements, nested, names);
+                }
+            },
+            Statement::While { statements, .. } 
+                => known &= bound_names(statements, nested, names),
+            Statement::For { params } => {
+                if !nested {
+                    names.insert(params.loop_var.clone());
+                }
+                known &= bound_names(&params.statements, nested, names);
+            },
+            Statement::Match { params } => {
+                for arm in &params.arms {
+                    if let MatchBody::Block { statements } = &arm.body {
+                        known &= bound_names(statements, nested, names);
+                    }
+                }
+            },
+            Statement::Try { statements, catch, finally } => {
+                known &= bound_names(statements, nested, names);
+                if let Some(catch) = catch {
+                    known &= bound_names(&catch.statements, nested, names);
+                }
+                if let Some(finally) = finally {
+                    known &= bound_names(finally, nested, names);
+                }
+            },
+            _ => (),
+        }
+    }
+    known
+}
+
+// The names an assignment target or match pattern binds
+fn pattern_names(pattern: &Expression, names: &mut HashSet<String>) {
+    match pattern {
+        Expression::Identifier { name, .. } if name != "_" => {
+            names.insert(name.clone());
+        },
+        Expression::List { items } => {
+            for item in items {
+                pattern_names(&item.expression, names);
+            }
+        },
+        Expression::Call { arguments, .. } => {
+            for argument in arguments {
+                pattern_names(&argument.expression, names);
+            }
+        },
+        _ => (),
+    }
+}
+
+// The variable an assignment target starts from, like `grid` in `grid[i].x`
+fn root_name(target: &Expression) -> Option<&String> {
+    match target {
+        Expression::Identifier { name, .. } => Some(name),
+        Expression::Index { expression, .. }
+        | Expression::Slice { expression, .. }
+        | Expression::Field { expression, .. } => root_name(expression),
+        _ => None
+    }
+}
+
+fn compatible(expected: &Type, found: &Type) -> bool {
+    matches!((expected, found),
+             (Type::Any, _) | (_, Type::Any) | (Type::Float, Type::Int))
+        || expected == found
+}
+
+// The type of `lhs operator rhs`, or None if the operator can't be used on
+// those types
+fn operation_type(operator: &Operator, lhs: &Type, rhs: &Type)
+    -> Option<Type> {
+    let is_number = |t: &Type| matches!(t, Type::Int | Type::Float);
+
+    match operator {
+        Operator::Equal | Operator::NotEqual | Operator::In
+            => Some(Type::Bool),
+        Operator::And | Operator::Or => {
+            let is_bool = |t: &Type| matches!(t, Type::Bool | Type::Any);
+            (is_bool(lhs) && is_bool(rhs)).then_some(Type::Bool)
+        },
+        Operator::LessThan | Operator::GreaterThan
+        | Operator::LessEqual | Operator::GreaterEqual => {
+            let comparable = match (lhs, rhs) {
+                (Type::Any, _) | (_, Type::Any) => true,
+                (Type::Char, Type::Char) | (Type::Str, Type::Str) => true,
+                (l, r) => is_number(l) && is_number(r),
+            };
+            comparable.then_some(Type::Bool)
+        },
+        _ => match (operator, lhs, rhs) {
+            (_, Type::Any, _) | (_, _, Type::Any) => Some(Type::Any),
+            // A negative exponent turns an Int into a Float
+            (Operator::Power, Type::Int, Type::Int) => Some(Type::Any),
+            (_, Type::Int, Type::Int) => Some(Type::Int),
+            (_, l, r) if is_number(l) && is_number(r) => Some(Type::Float),
+            (Operator::Plus, Type::Str, Type::Str | Type::Char)
+            | (Operator::Plus, Type::Char, Type::Str)
+            | (Operator::Times, Type::Str, Type::Int)
+            | (Operator::Times, Type::Int, Type::Str) => Some(Type::Str),
+            (Operator::Plus, Type::List, Type::List)
+            | (Operator::Times, Type::List, Type::Int)
+            | (Operator::Times, Type::Int, Type::List) => Some(Type::List),
+            _ => None
+        }
+    }
+}
+
+impl Checker {
+    fn error(&mut self, location: Location, message: String) {
+        self.errors.push(TypeError { location, message });
+    }
+
+    fn expect(&mut self, expected: &Type, found: &Type, location: Location,
+              what: &str) {
+        if !compatible(expected, found) {
+            self.error(location, format!("Expected {} for {}, found {}",
+                                         expected, what, found));
+        }
+    }
+
+    fn scope(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("there is always a global scope")
+    }
+
+    // The index of the scope a name is read from, among the first `end`
+    fn owner(&self, name: &str, end: usize) -> Option<usize> {
+        for (idx, scope) in self.scopes[..end].iter().enumerate().rev() {
+            if let Some(&target) = scope.outer.get(name) {
+                return Some(target);
+            }
+            if scope.names.contains_key(name) {
+                return Some(idx);
+            }
+        }
+        None
+    }
+
+    fn lookup(&self, name: &str) -> Type {
+        self.owner(name, self.scopes.len())
+            .and_then(|idx| self.scopes[idx].names.get(name))
+            .cloned()
+            .unwrap_or(Type::Any)
+    }
+
+    // With `strict`, reading a name that nothing has bound yet is an error.
+    // A function can also read the names bound later around it, since it
+    // may not be called until they are
+    fn read(&mut self, name: &str, location: Location) -> Type {
+        let around = &self.bodies[..self.bodies.len() - 1];
+        if self.strict && !self.imported 
+            && self.owner(name, self.scopes.len()).is_none()
+            && !around.iter().any(|names| names.contains(name)) {
+            self.error(location, format!("'{}' is not declared", name));
+        }
+        self.lookup(name)
+    }
+
+    // Only the innermost binding of a name can be the function being called
+    fn signature(&self, name: &str) -> Option<Signature> {
+        let idx = self.owner(name, self.scopes.len())?;
+        self.scopes[idx].signatures.get(name).cloned()
+    }
+
+    fn function_scope(&self) -> usize {
+        self.scopes.iter().rposition(|scope| !scope.is_block).unwrap_or(0)
+    }
+
+    fn declare(&mut self, name: &str, ty: Type) {
+        let innermost = self.scopes.len() - 1;
+        self.declare_in(innermost, name, ty);
+    }
+
+    fn declare_in(&mut self, idx: usize, name: &str, ty: Type) {
+        let scope = &mut self.scopes[idx];
+        scope.names.insert(name.to_string(), ty);
+        scope.signatures.remove(name);
+    }
+
+    // The scope a name assigned without let or const ends up in: the one
+    // it is bound in, looking outwards through blocks, or else the scope of
+    // the enclosing function, unless it made the name global or nonlocal
+    fn var_scope(&self, name: &str) -> usize {
+        for (idx, scope) in self.scopes.iter().enumerate().rev() {
+            if let Some(&target) = scope.outer.get(name) {
+                return target;
+            }
+            if scope.names.contains_key(name) || !scope.is_block {
+                return idx;
+            }
+        }
+        0
+    }
+
+    // Reports assigning to a constant, directly or to an element or field
+    // inside it. Elements are changed in place, so for those the constant
+    // is the one the name is read from
+    fn check_target(&mut self, target: &Expression, location: Location) {
+        let Some(name) = root_name(target) else { return };
+        let idx = match target {
+            Expression::Identifier { .. } => Some(self.var_scope(name)),
+            _ => self.owner(name, self.scopes.len()),
+        };
+
+        if idx.is_some_and(|idx| self.scopes[idx].constants.contains(name)) {
+            self.error(location, format!(
+                "Cannot assign to constant '{}'", name));
+        }
+    }
+
+    // Names bound without an annotation, by loops and destructuring
+    fn bind(&mut self, target: &Expression, location: Option<Location>) {
+        if let Some(location) = location {
+            self.check_target(target, location);
+        }
+
+        match target {
+            Expression::Identifier { name, .. } => {
+                let idx = self.var_scope(name);
+                if !self.scopes[idx].names.contains_key(name) {
+                    self.declare_in(idx, name, Type::Any);
+                }
+            },
+            Expression::List { items } => {
+                for item in items {
+                    self.bind(&item.expression, location);
+                }
+            },
+            target => { self.expression(target); },
+        }
+    }
+
+    fn block(&mut self, statements: &[Statement]) {
+        self.scopes.push(Scope { is_block: true, ..Scope::default() });
+        self.statements(statements);
+        self.scopes.pop();
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Import { .. } => self.imported = true,
+            // Definitions are bound like assignments without let or const
+            Statement::StructDefinition { name, .. }
+            | Statement::EnumDefinition { name, .. } => {
+                let idx = self.var_scope(name);
+                self.declare_in(idx, name, Type::Any);
+            },
+            Statement::Break { .. }
+            | Statement::Continue { .. } => (),
+            Statement::Expression { expression } => {
+                self.expression(expression);
+            },
+            Statement::Global { names } => {
+                let function = self.function_scope();
+                if function != 0 {
+                    for name in names {
+                        self.scopes[function].outer.insert(name.clone(), 0);
+                    }
+                }
+            },
+            // A name with no binding yet is reported when the program runs,
+            // since it could come from an import
+            Statement::Nonlocal { names, .. } => {
+                let function = self.function_scope();
+                for name in names {
+                    if let Some(idx) = self.owner(name, function) {
+                        self.scopes[function].outer.insert(name.clone(), idx);
+                    }
+                }
+            },
+            Statement::Assignment { lhs, annotation, rhs, location }
+                => self.assignment(lhs, annotation.as_ref(), rhs, *location),
+            Statement::Declaration { 
+                lhs, annotation, rhs, is_const, location 
+            } => self.declaration(lhs, annotation.as_ref(), rhs, *is_const, 
+                                  *location),
+            Statement::OperatorAssignment { lhs, operator, rhs, location } => {
+                let found = self.expression(rhs);
+                self.check_target(lhs, *location);
+                let Expression::Identifier { name, location } = lhs else {
+                    self.expression(lhs);
+                    return;
+                };
+
+                let expected = self.read(name, *location);
+                match operation_type(operator, &expected, &found) {
+                    Some(result) => self.expect(&expected, &result, *location,
+                                                &format!("'{}'", name)),
+                    None => self.error(*location, format!(
+                        "Cannot use '{}' on {} and {}",
+                        operator, expected, found)),
+                }
+            },
+            Statement::If { params } => {
+                self.expression(&params.condition);
+                self.block(&params.statements);
+                let (conditions, branches) = &params.elif_data;
+                for (condition, statements) in conditions.iter().zip(branches) {
+                    self.expression(condition);
+                    self.block(statements);
+                }
+                if let Some(else_statements) = &params.else_statements {
+                    self.block(else_statements);
+                }
+            },
+            Statement::While { condition, statements, .. } => {
+                self.expression(condition);
+                self.block(statements);
+            },
+            Statement::For { params } => {
+                self.expression(&params.iterate_expression);
+                self.bind(&Expression::Identifier {
+                    name: params.loop_var.clone(),
+                    location: params.location,
+                }, Some(params.location));
+                self.block(&params.statements);
+            },
+            Statement::FunctionDefinition {
+                name, arguments, returns, statements, location
+            } => {
+                let signature = Signature {
+                    name: name.clone(),
+                    parameters: arguments.clone(),
+                    returns: returns.clone(),
+                };
+                let idx = self.var_scope(name);
+                self.declare_in(idx, name, Type::Function);
+                self.scopes[idx].signatures.insert(name.clone(), signature);
+                self.function(name, arguments, returns, statements,
+                              *location);
+            },
+            Statement::Match { params } => { self.match_block(params); },
+            Statement::Return { expression, location } => {
+                let found = match expression {
+                    Some(expression) => self.expression(expression),
+                    None => Type::Null
+                };
+
+                if let Some((name, Some(expected))) = self.functions.last() {
+                    let (name, expected) = (name.clone(), expected.clone());
+                    self.expect(&expected, &found, *location,
+                                &format!("the return value of '{}'", name));
+                }
+            },
+            Statement::Throw { expression, .. } => {
+                self.expression(expression);
+            },
+            Statement::Try { statements, catch, finally } => {
+                self.block(statements);
+                if let Some(catch) = catch {
+                    self.scopes.push(Scope { 
+                        is_block: true, 
+                        ..Scope::default() 
+                    });
+                    self.declare(&catch.name, Type::Named {
+                        name: "Error".to_string()
+                    });
+                    self.statements(&catch.statements);
+                    self.scopes.pop();
+                }
+                if let Some(finally) = finally {
+                    self.block(finally);
+                }
+            },
+        }
+    }
+
+    fn assignment(&mut self, lhs: &Expression, annotation: Option<&Type>,
+                  rhs: &Expression, location: Location) {
+        let found = self.expression(rhs);
+        let Expression::Identifier { name, .. } = lhs else {
+            self.bind(lhs, Some(location));
+            return;
+        };
+
+        // Assigning to a name that isn't bound in the blocks around it
+        // creates a new variable in the function's scope, like it does when
+        // the program runs
+        self.check_target(lhs, location);
+        let idx = self.var_scope(name);
+        let declared = self.scopes[idx].names.get(name).cloned();
+        let ty = match (annotation, declared) {
+            (Some(annotation), _) => {
+                self.expect(annotation, &found, location,
+                            &format!("'{}'", name));
+                annotation.clone()
+            },
+            (None, Some(declared)) => {
+                self.expect(&declared, &found, location,
+                            &format!("'{}'", name));
+                declared
+            },
+            (None, None) => {
+                if self.strict && name != "_" {
+                    self.error(location, format!(
+                        "'{}' is not declared", name));
+                }
+                Type::Any
+            },
+        };
+        self.define(idx, name, ty, rhs);
+    }
+
+    fn declaration(&mut self, lhs: &Expression, annotation: Option<&Type>,
+                   rhs: &Expression, is_const: bool, location: Location) {
+        let found = self.expression(rhs);
+        let Expression::Identifier { name, .. } = lhs else {
+            self.declare_pattern(lhs, is_const, location);
+            return;
+        };
+
+        let ty = match annotation {
+            Some(annotation) => {
+                self.expect(annotation, &found, location,
+                            &format!("'{}'", name));
+                annotation.clone()
+            },
+            None => {
+                if self.strict && name != "_" {
+                    self.error(location, format!(
+                        "'{}' needs a type annotation", name));
+                }
+                Type::Any
+            },
+        };
+        self.declare_pattern(lhs, is_const, location);
+        self.define(self.scopes.len() - 1, name, ty, rhs);
+    }
+
+    // Declares the names in a `let` or `const` target in the innermost scope
+    fn declare_pattern(&mut self, target: &Expression, is_const: bool,
+                       location: Location) {
+        match target {
+            Expression::Identifier { name, .. } if name == "_" => (),
+            Expression::Identifier { name, .. } => {
+                if self.scope().names.contains_key(name) {
+                    self.error(location, format!(
+                        "'{}' is already declared in this scope", name));
+                }
+                self.declare(name, Type::Any);
+                if is_const {
+                    self.scope().constants.insert(name.clone());
+                }
+            },
+            Expression::List { items } => {
+                for item in items {
+                    self.declare_pattern(&item.expression, is_const, 
+                                         location);
+                }
+            },
+            target => { self.expression(target); },
+        }
+    }
+
+    fn define(&mut self, idx: usize, name: &str, ty: Type, 
+              rhs: &Expression) {
+        self.declare_in(idx, name, ty);
+
+        // Keep what a lambda expects so calls through the name are checked
+        if let Expression::Lambda { arguments, returns, .. } = rhs {
+            self.scopes[idx].signatures.insert(name.to_string(), Signature {
+                name: name.to_string(),
+                parameters: arguments.clone(),
+                returns: returns.clone(),
+            });
+        }
+    }
+
+    fn function(&mut self, name: &str, arguments: &[Parameter],
+                returns: &Option<Type>, statements: &[Statement],
+                location: Location) {
+        if self.strict {
+            for parameter in arguments.iter()
+                .filter(|p| p.annotation.is_none() && !p.is_rest) {
+                self.error(location, format!(
+                    "Parameter '{}' of '{}' needs a type annotation",
+                    parameter.name, name));
+            }
+            if returns.is_none() {
+                self.error(location, format!(
+                    "'{}' needs a return type annotation", name));
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for parameter in arguments {
+            if !seen.insert(&parameter.name) {
+                self.error(location, format!(
+                    "Parameter '{}' of '{}' is given more than once",
+                    parameter.name, name));
+            }
+        }
+
+        // Defaults run in the function's scope after the parameters before
+        // them are bound
+        self.scopes.push(Scope::default());
+        for parameter in arguments {
+            if let Some(default) = &parameter.default {
+                let found = self.expression(default);
+                if let Some(expected) = &parameter.annotation {
+                    self.expect(expected, &found, location, 
+                                &format!("the default of '{}'", 
+                                         parameter.name));
+                }
+            }
+
+            let ty = match &parameter.annotation {
+                _ if parameter.is_rest => Type::List,
+                Some(annotation) => annotation.clone(),
+                None => Type::Any,
+            };
+            self.declare(&parameter.name, ty);
+        }
+
+        let mut names = HashSet::new();
+        self.imported |= !bound_names(statements, false, &mut names);
+        self.bodies.push(names);
+        self.functions.push((name.to_string(), returns.clone()));
+        self.statements(statements);
+        self.functions.pop();
+        self.bodies.pop();
+        self.scopes.pop();
+    }
+
+    // Pairs the arguments up with the parameters the way the call will when
+    // it runs
+    fn call(&mut self, function: &Expression, arguments: &[Argument],
+            location: Location) -> Type {
+        let found: Vec<(Option<&String>, Type)> = arguments.iter()
+            .map(|a| (a.name.as_ref(), self.expression(&a.expression)))
+            .collect();
+
+        let signature = match function {
+            Expression::Identifier { name, location } => {
+                self.read(name, *location);
+                self.signature(name)
+            },
+            function => {
+                self.expression(function);
+                None
+            }
+        };
+        let Some(signature) = signature else { return Type::Any };
+        let name = &signature.name;
+
+        // Which parameters a spread fills in isn't known until it runs
+        let mut spread = false;
+        for (argument, (_, found)) in arguments.iter().zip(&found) {
+            if !argument.is_spread {
+                continue;
+            }
+            spread = true;
+            if !matches!(found, Type::List | Type::Range | Type::Map 
+                                | Type::Any) {
+                self.error(location, format!(
+                    "Cannot spread {} into arguments", found));
+            }
+        }
+        if spread {
+            return signature.returns.clone().unwrap_or(Type::Any);
+        }
+
+        for (argument, _) in &found {
+            let Some(argument) = argument else { continue };
+            if !signature.parameters.iter()
+                .any(|p| &p.name == *argument && !p.is_rest) {
+                self.error(location, format!(
+                    "'{}' has no parameter named '{}'", name, argument));
+            }
+        }
+
+        let mut positional = found.iter()
+            .filter(|(argument, _)| argument.is_none())
+            .map(|(_, ty)| ty);
+        for parameter in &signature.parameters {
+            if parameter.is_rest {
+                positional.by_ref().for_each(drop);
+                continue;
+            }
+
+            let by_name = found.iter()
+                .find(|(argument, _)| *argument == Some(&parameter.name))
+                .map(|(_, ty)| ty);
+            let found = match (positional.next(), by_name) {
+                (Some(found), None) | (None, Some(found)) => found,
+                (Some(_), Some(_)) => {
+                    self.error(location, format!(
+                        "Argument '{}' of '{}' is given more than once",
+                        parameter.name, name));
+                    continue;
+                },
+                (None, None) => {
+                    if parameter.default.is_none() {
+                        self.error(location, format!(
+                            "Missing argument '{}' for '{}'",
+                            parameter.name, name));
+                    }
+                    continue;
+                },
+            };
+
+            if let Some(expected) = &parameter.annotation {
+                self.expect(expected, found, location,
+                            &format!("argument '{}' of '{}'",
+                                     parameter.name, name));
+            }
+        }
+
+        let extra = positional.count();
+        if extra > 0 {
+            let count = found.iter().filter(|(a, _)| a.is_none()).count();
+            let accepted = signature.parameters.iter()
+                .filter(|p| !p.is_rest)
+                .count();
+            self.error(location, format!(
+                "'{}' takes at most {} arguments, got {}",
+                name, accepted, count));
+        }
+
+        signature.returns.clone().unwrap_or(Type::Any)
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Type {
+        match expression {
+            Expression::Int { .. } => Type::Int,
+            Expression::String { .. } => Type::Str,
+            Expression::Boolean { .. } => Type::Bool,
+            Expression::Float { .. } => Type::Float,
+            Expression::Character { .. } => Type::Char,
+            Expression::FString { parts } => {
+                for part in parts {
+                    if let FStringPart::Value { expression, .. } = part {
+                        self.expression(expression);
+                    }
+                }
+                Type::Str
+            },
+            Expression::List { items } => {
+                for item in items {
+                    self.expression(&item.expression);
+                }
+                Type::List
+            },
+            Expression::Map { entries } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+                Type::Map
+            },
+            Expression::Identifier { name, location } 
+                => self.read(name, *location),
+            Expression::Call { function, arguments, location }
+                => self.call(function, arguments, *location),
+            Expression::Operation { lhs, rhs, operator, location } => {
+                let lhs = self.expression(lhs);
+                let rhs = self.expression(rhs);
+                match operation_type(operator, &lhs, &rhs) {
+                    Some(result) => result,
+                    None => {
+                        self.error(*location, format!(
+                            "Cannot use '{}' on {} and {}",
+                            operator, lhs, rhs));
+                        Type::Any
+                    }
+                }
+            },
+            Expression::Prefix { name, rhs, location, .. } => {
+                self.expression(rhs);
+                let target = Expression::Identifier {
+                    name: name.clone(),
+                    location: *location,
+                };
+                self.check_target(&target, *location);
+                self.read(name, *location)
+            },
+            Expression::Not { rhs } => {
+                self.expression(rhs);
+                Type::Bool
+            },
+            Expression::Index { expression, idx_exp } => {
+                let container = self.expression(expression);
+                self.expression(idx_exp);
+                match container {
+                    Type::Str => Type::Char,
+                    Type::Range => Type::Int,
+                    _ => Type::Any
+                }
+            },
+            Expression::Slice { expression, start, end, step } => {
+                let container = self.expression(expression);
+                for bound in [start, end, step].into_iter().flatten() {
+                    self.expression(bound);
+                }
+                match container {
+                    Type::Str => Type::Str,
+                    Type::List => Type::List,
+                    _ => Type::Any
+                }
+            },
+            Expression::StructLiteral { name, fields } => {
+                for (_, value) in fields {
+                    self.expression(value);
+                }
+                Type::Named { name: name.clone() }
+            },
+            Expression::Field { expression, .. } => {
+                self.expression(expression);
+                Type::Any
+            },
+            Expression::Lambda { arguments, returns, statements, location } => {
+                self.function("lambda", arguments, returns, statements,
+                              *location);
+                Type::Function
+            },
+            Expression::Comprehension { iterate_exp, clauses } => {
+                self.scopes.push(Scope::default());
+                self.clauses(clauses);
+                self.expression(iterate_exp);
+                self.scopes.pop();
+                Type::List
+            },
+            Expression::MapComprehension { key, value, clauses } => {
+                self.scopes.push(Scope::default());
+                self.clauses(clauses);
+                self.expression(key);
+                self.expression(value);
+                self.scopes.pop();
+                Type::Map
+            },
+            Expression::Match { params } => self.match_block(params),
+        }
+    }
+
+    fn clauses(&mut self, clauses: &[ComprehensionClause]) {
+        for clause in clauses {
+            match clause {
+                ComprehensionClause::For { target, iterable } => {
+                    self.expression(iterable);
+                    self.bind(target, None);
+                },
+                ComprehensionClause::If { condition } => {
+                    self.expression(condition);
+                },
+            }
+        }
+    }
+
+    // Patterns aren't checked, only the names they bind are declared, in
+    // the block the arm runs in
+    fn match_block(&mut self, params: &MatchBlock) -> Type {
+        self.expression(&params.subject);
+        for arm in &params.arms {
+            self.scopes.push(Scope { is_block: true, ..Scope::default() });
+            let mut names = HashSet::new();
+            pattern_names(&arm.pattern, &mut names);
+            for name in names {
+                self.declare(&name, Type::Any);
+            }
+            match &arm.body {
+                MatchBody::Expression { expression } => {
+                    self.expression(expression);
+                },
+                MatchBody::Block { statements } => self.statements(statements),
+            }
+            self.scopes.pop();
+        }
+        Type::Any
+    }
+}
@@ -1,7 +1,23 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
-use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign };
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Rem, Sub, 
+               SubAssign };
+use std::rc::Rc;
 
-use crate::ast::{Expression, Statement};
+use crate::ast::{Align, EnumVariant, FormatSpec, Parameter, Statement};
+use crate::environment::Environment;
+use crate::error::Error;
+
+// Lists, maps and structs are shared between every variable, argument and
+// element that holds them, so changing one in place is seen through all of
+// them. Copying one takes an explicit call to the copy builtin
+pub type Shared<T> = Rc<RefCell<T>>;
+
+pub fn shared<T>(value: T) -> Shared<T> {
+    Rc::new(RefCell::new(value))
+}
 
 #[derive(Debug)]
 pub enum Value {
@@ -15,11 +31,311 @@ pub enum Value {
     #[allow(dead_code)]
     Char{c: char},
     #[allow(dead_code)]
-    List{e: Vec<Value>},
-    Function{name: String, f: fn(Vec<Value>) -> Result<Value, String>},
+    List{e: Shared<Vec<Value>>},
+    Map{m: Shared<BTreeMap<MapKey, Value>>},
+    // Made by range and range_step. Its values are worked out as they are
+    // needed instead of being stored
+    Range{start: i32, end: i32, step: i32},
+    StructDef{name: String, fields: Vec<String>},
+    Struct{name: String, fields: Shared<Vec<(String, Value)>>},
+    EnumDef{name: String, variants: Vec<EnumVariant>},
+    Enum{name: String, variant: String, values: Vec<Value>},
+    // What `Shape.Circle` evaluates to when the variant carries values
+    VariantConstructor{name: String, variant: String, arity: usize},
+    Function{name: String, f: fn(Vec<Value>) -> Result<Value, Error>},
     #[allow(dead_code)]
     UserDefFunction{name: String, statements: Vec<Statement>, 
-        arguments: Vec<String>, return_expression: Option<Expression> }
+        arguments: Vec<Parameter>, env: Environment },
+    // A caught error, or one made by the error builtin to be thrown
+    Error{error: Box<Error>},
+}
+
+// The subset of values that can be used as map keys
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MapKey {
+    Int{v: i32},
+    Str{s: String},
+    Char{c: char},
+    Bool{b: bool},
+}
+
+impl TryFrom<Value> for MapKey {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Int { v } => Ok(MapKey::Int { v }),
+            Value::Str { s } => Ok(MapKey::Str { s }),
+            Value::Char { c } => Ok(MapKey::Char { c }),
+            Value::Bool { b } => Ok(MapKey::Bool { b }),
+            _ => Err(Error::type_error(format!("{} cannot be used as a map \
+                                                key", value.type_name())))
+        }
+    }
+}
+
+impl From<MapKey> for Value {
+    fn from(key: MapKey) -> Self {
+        match key {
+            MapKey::Int { v } => Value::Int { v },
+            MapKey::Str { s } => Value::Str { s },
+            MapKey::Char { c } => Value::Char { c },
+            MapKey::Bool { b } => Value::Bool { b },
+        }
+    }
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "Null",
+            Value::Int { .. } => "Int",
+            Value::Str { .. } => "String",
+            Value::Bool { .. } => "Bool",
+            Value::Float { .. } => "Float",
+            Value::Char { .. } => "Char",
+            Value::List { .. } => "List",
+            Value::Map { .. } => "Map",
+            Value::Range { .. } => "Range",
+            Value::StructDef { .. } => "Struct",
+            Value::Struct { .. } => "Struct",
+            Value::EnumDef { .. } => "Enum",
+            Value::Enum { .. } => "Enum",
+            Value::VariantConstructor { .. } => "Function",
+            Value::Function { .. } => "Function",
+            Value::UserDefFunction { .. } => "Function",
+            Value::Error { .. } => "Error",
+        }
+    }
+
+    // How many values a List, String, Map or Range holds
+    pub fn len(&self) -> Option<usize> {
+        match self {
+            Value::List { e } => Some(e.borrow().len()),
+            Value::Str { s } => Some(s.chars().count()),
+            Value::Map { m } => Some(m.borrow().len()),
+            Value::Range { start, end, step } 
+                => Some(range_len(*start, *end, *step)),
+            _ => None
+        }
+    }
+
+    pub fn is_iterable(&self) -> bool {
+        matches!(self, Value::List { .. } | Value::Str { .. } 
+                       | Value::Map { .. } | Value::Range { .. })
+    }
+
+    // Whether item is one of the values that iterating over self yields,
+    // or for strings whether it is a substring
+    pub fn contains(&self, item: &Value) -> Result<bool, Error> {
+        match (self, item) {
+            (Value::List { e }, item) => Ok(e.borrow().contains(item)),
+            (Value::Str { s }, Value::Char { c }) => Ok(s.contains(*c)),
+            (Value::Str { s }, Value::Str { s: sub }) 
+                => Ok(s.contains(sub.as_str())),
+            (Value::Map { m }, key) 
+                => Ok(m.borrow()
+                       .contains_key(&MapKey::try_from(key.clone())?)),
+            (Value::Range { start, end, step }, Value::Int { v }) => {
+                let (start, end, step, v) = (i64::from(*start), 
+                    i64::from(*end), i64::from(*step), i64::from(*v));
+                let in_bounds = if step > 0 { start <= v && v < end } 
+                                else { end < v && v <= start };
+                Ok(in_bounds && (v - start) % step == 0)
+            },
+            (Value::Range { .. }, _) => Ok(false),
+            _ => Err(Error::type_error(format!("Cannot look for {} in {}", 
+                                               item.type_name(), 
+                                               self.type_name())))
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Value::Int { v } => *v == 0,
+            Value::Float { f } => *f == 0.0,
+            _ => false
+        }
+    }
+
+    // Raises self to the power of rhs. An Int raised to a negative Int can't
+    // stay an Int, so it is promoted to a Float. Overflow yields Null
+    pub fn pow(&self, rhs: &Value) -> Value {
+        match (self, rhs) {
+            (Value::Int { v: lv }, Value::Int { v: rv }) => {
+                match u32::try_from(*rv) {
+                    Ok(exp) => match lv.checked_pow(exp) {
+                        Some(v) => Value::Int{v},
+                        None => Value::Null
+                    },
+                    Err(_) => Value::Float{f: f64::from(*lv).powi(*rv)}
+                }
+            },
+            (Value::Float { f: lf }, Value::Float { f: rf }) 
+                => Value::Float{f: lf.powf(*rf)},
+            (Value::Int { v }, Value::Float { f }) 
+                => Value::Float{f: f64::from(*v).powf(*f)},
+            (Value::Float { f }, Value::Int { v }) 
+                => Value::Float{f: f.powi(*v)},
+            _ => Value::Null
+        }
+    }
+
+    // Formats the value the way an f-string spec such as `{pi:>8.3}` asks.
+    // The precision is the number of decimals for numbers and the maximum
+    // length for strings. Numbers are aligned right by default
+    pub fn format(&self, spec: &FormatSpec) -> Result<String, Error> {
+        let is_number = matches!(self, Value::Int { .. } | Value::Float { .. });
+        let text = match (self, spec.precision) {
+            (_, None) => self.to_string(),
+            (Value::Int { v }, Some(p)) => format!("{:.*}", p, f64::from(*v)),
+            (Value::Float { f }, Some(p)) => format!("{:.*}", p, f),
+            (Value::Str { s }, Some(p)) => s.chars().take(p).collect(),
+            (v, Some(_)) => return Err(Error::type_error(format!(
+                "Cannot format {} with a precision", v.type_name())))
+        };
+
+        let padding = spec.width.saturating_sub(text.chars().count());
+        if spec.zero && spec.align.is_none() && is_number {
+            let (sign, digits) = match text.strip_prefix('-') {
+                Some(digits) => ("-", digits),
+                None => ("", text.as_str())
+            };
+            return Ok(format!("{}{}{}", sign, "0".repeat(padding), digits));
+        }
+
+        let fill = |n: usize| spec.fill.to_string().repeat(n);
+        let align = match &spec.align {
+            Some(align) => align,
+            None if is_number => &Align::Right,
+            None => &Align::Left,
+        };
+        Ok(match align {
+            Align::Left => format!("{}{}", text, fill(padding)),
+            Align::Right => format!("{}{}", fill(padding), text),
+            Align::Center => format!("{}{}{}", fill(padding / 2), text, 
+                                     fill(padding - padding / 2)),
+        })
+    }
+
+    // The string or list written `count` times in a row. A count below one
+    // gives an empty string or list
+    pub fn repeat(&self, count: i32) -> Value {
+        let count = usize::try_from(count).unwrap_or(0);
+        match self {
+            Value::Str { s } => Value::Str{s: s.repeat(count)},
+            Value::List { e } => {
+                let e = e.borrow();
+                Value::List{e: shared(
+                    e.iter().cloned().cycle().take(e.len() * count).collect()
+                )}
+            },
+            _ => Value::Null
+        }
+    }
+
+    // Division rounded towards negative infinity, so that
+    // a == (a ~/ b) * b + a % b holds for every pair of numbers. Overflow
+    // yields Null
+    pub fn floor_div(&self, rhs: &Value) -> Value {
+        match (self, rhs) {
+            (Value::Int { v: lv }, Value::Int { v: rv }) => {
+                let (Some(q), Some(r)) = (lv.checked_div(*rv), 
+                                          lv.checked_rem(*rv)) 
+                    else { return Value::Null };
+                if r != 0 && (r < 0) != (*rv < 0) {
+                    Value::Int{v: q - 1}
+                } else {
+                    Value::Int{v: q}
+                }
+            },
+            (Value::Float { f: lf }, Value::Float { f: rf }) 
+                => Value::Float{f: (lf / rf).floor()},
+            (Value::Int { v }, Value::Float { f }) 
+                => Value::Float{f: (f64::from(*v) / f).floor()},
+            (Value::Float { f }, Value::Int { v }) 
+                => Value::Float{f: (f / f64::from(*v)).floor()},
+            _ => Value::Null
+        }
+    }
+
+    // A copy that shares nothing with the original, down to the lists, maps
+    // and structs inside it
+    pub fn deep_copy(&self) -> Value {
+        self.copy_with(&mut HashMap::new())
+    }
+
+    // `copies` holds what each shared value has been copied to, so a value
+    // that is held twice, or holds itself, keeps that shape in the copy
+    fn copy_with(&self, copies: &mut HashMap<*const (), Value>) -> Value {
+        let key = match self {
+            Value::List { e } => Rc::as_ptr(e).cast::<()>(),
+            Value::Map { m } => Rc::as_ptr(m).cast::<()>(),
+            Value::Struct { fields, .. } => Rc::as_ptr(fields).cast::<()>(),
+            Value::Enum { name, variant, values } => return Value::Enum { 
+                name: name.clone(), 
+                variant: variant.clone(), 
+                values: values.iter().map(|v| v.copy_with(copies)).collect()
+            },
+            _ => return self.clone()
+        };
+        if let Some(copy) = copies.get(&key) {
+            return copy.clone();
+        }
+
+        match self {
+            Value::List { e } => {
+                let copy = shared(vec![]);
+                copies.insert(key, Value::List { e: copy.clone() });
+                let items = e.borrow().iter()
+                    .map(|v| v.copy_with(copies))
+                    .collect();
+                *copy.borrow_mut() = items;
+                Value::List { e: copy }
+            },
+            Value::Map { m } => {
+                let copy = shared(BTreeMap::new());
+                copies.insert(key, Value::Map { m: copy.clone() });
+                let entries = m.borrow().iter()
+                    .map(|(k, v)| (k.clone(), v.copy_with(copies)))
+                    .collect();
+                *copy.borrow_mut() = entries;
+                Value::Map { m: copy }
+            },
+            Value::Struct { name, fields } => {
+                let copy = shared(vec![]);
+                copies.insert(key, Value::Struct { name: name.clone(), 
+                                                   fields: copy.clone() });
+                let values = fields.borrow().iter()
+                    .map(|(field, v)| (field.clone(), v.copy_with(copies)))
+                    .collect();
+                *copy.borrow_mut() = values;
+                Value::Struct { name: name.clone(), fields: copy }
+            },
+            _ => self.clone()
+        }
+    }
+}
+
+thread_local! {
+    // The lists, maps and structs being displayed, innermost last
+    static DISPLAYING: RefCell<Vec<*const ()>> = const { 
+        RefCell::new(Vec::new()) 
+    };
+}
+
+// Displays a shared value with `show`, or with `placeholder` if the value
+// is inside itself and already being displayed further out
+fn display_once(ptr: *const (), placeholder: &str, 
+                show: impl FnOnce() -> String) -> String {
+    if DISPLAYING.with(|d| d.borrow().contains(&ptr)) {
+        return placeholder.to_string();
+    }
+
+    DISPLAYING.with(|d| d.borrow_mut().push(ptr));
+    let text = show();
+    DISPLAYING.with(|d| d.borrow_mut().pop());
+    text
 }
 
 impl fmt::Display for Value {
@@ -39,25 +355,89 @@ impl fmt::Display for Value {
             Value::Char { c } 
                 => to_print = format!("{}", c),
             Value::List { e } => {
-                to_print = String::new();
-                to_print.push('[');
-                for (idx, val) in e.iter().enumerate() {
-                    to_print.push_str(&val.to_string());
-                    if idx != e.len() - 1 {
-                        to_print.push_str(", ");
-                    }
+                to_print = display_once(Rc::as_ptr(e).cast(), "[...]", || {
+                    let items: Vec<String> = e.borrow().iter()
+                        .map(|v| v.to_string())
+                        .collect();
+                    format!("[{}]", items.join(", "))
+                });
+            },
+            Value::Map { m } => {
+                to_print = display_once(Rc::as_ptr(m).cast(), "{...}", || {
+                    let entries: Vec<String> = m.borrow().iter()
+                        .map(|(k, v)| format!("{}: {}", 
+                                              Value::from(k.clone()), v))
+                        .collect();
+                    format!("{{{}}}", entries.join(", "))
+                });
+            },
+            Value::Range { start, end, step: 1 } 
+                => to_print = format!("range({}, {})", start, end),
+            Value::Range { start, end, step } 
+                => to_print = format!("range_step({}, {}, {})", 
+                                      start, end, step),
+            Value::StructDef { name, .. } 
+                => to_print = format!("Struct \"{}\"", name),
+            Value::Struct { name, fields } => {
+                let placeholder = format!("{}{{...}}", name);
+                to_print = display_once(Rc::as_ptr(fields).cast(), 
+                                        &placeholder, || {
+                    let fields: Vec<String> = fields.borrow().iter()
+                        .map(|(field, v)| format!("{}: {}", field, v))
+                        .collect();
+                    format!("{}{{{}}}", name, fields.join(", "))
+                });
+            },
+            Value::EnumDef { name, .. } 
+                => to_print = format!("Enum \"{}\"", name),
+            Value::Enum { name, variant, values } => {
+                to_print = format!("{}.{}", name, variant);
+                if !values.is_empty() {
+                    let values: Vec<String> = values.iter()
+                        .map(|v| v.to_string())
+                        .collect();
+                    to_print.push_str(&format!("({})", values.join(", ")));
                 }
-                to_print.push(']');
             },
+            Value::VariantConstructor { name, variant, .. } 
+                => to_print = format!("Function \"{}.{}\"", name, variant),
             Value::Function { name, .. } 
                 => to_print = format!("Function \"{}\"", name),
             Value::UserDefFunction { name, .. } 
                 => to_print = format!("Function \"{}\"", name),
+            Value::Error { error } 
+                => to_print = error.to_string(),
         };
         write!(f, "{}", to_print)
     }
 }
 
+thread_local! {
+    // The pairs of lists, maps and structs being compared, innermost last
+    static COMPARING: RefCell<Vec<(*const (), *const ())>> = const { 
+        RefCell::new(Vec::new()) 
+    };
+}
+
+// Compares the contents of two shared values. A pair that is already being
+// compared further out is taken to be equal, so values inside themselves
+// don't recurse forever
+fn shared_eq<T: PartialEq>(lhs: &Shared<T>, rhs: &Shared<T>) -> bool {
+    if Rc::ptr_eq(lhs, rhs) {
+        return true;
+    }
+
+    let pair = (Rc::as_ptr(lhs).cast::<()>(), Rc::as_ptr(rhs).cast::<()>());
+    if COMPARING.with(|c| c.borrow().contains(&pair)) {
+        return true;
+    }
+
+    COMPARING.with(|c| c.borrow_mut().push(pair));
+    let equal = *lhs.borrow() == *rhs.borrow();
+    COMPARING.with(|c| c.borrow_mut().pop());
+    equal
+}
+
 pub struct ValueIterator{
     pub value: Value,
     index: usize,
@@ -69,13 +449,17 @@ impl IntoIterator for Value {
     type IntoIter = ValueIterator;
 
     fn into_iter(self) -> Self::IntoIter {
-        match &self {
-            Value::List { .. } => ValueIterator{
+        match self {
+            Value::List { .. } | Value::Str { .. } | Value::Range { .. } 
+                => ValueIterator{
                 value: self, 
                 index: 0
             },
-            Value::Str { .. } => ValueIterator{
-                value: self, 
+            // Maps yield their keys
+            Value::Map { m } => ValueIterator{
+                value: Value::List{e: shared(
+                    m.borrow().keys().cloned().map(Value::from).collect()
+                )},
                 index: 0
             },
             _ => {
@@ -95,22 +479,45 @@ impl Iterator for ValueIterator {
         let val = &self.value;
         match val {
             Value::List { e } => {
-                let item = e[self.index].clone();
+                let item = e.borrow().get(self.index)?.clone();
                 self.index += 1;
                 Some(item)
             },
+            // For strings the index is a byte offset
             Value::Str { s } => {
-                let mut chars = s.chars();
-                let item = chars.nth(self.index)
-                    .expect("Error getting char from string");
-                self.index += 1;
+                let item = s[self.index..].chars().next()?;
+                self.index += item.len_utf8();
                 Some(Value::Char{c: item})
             },
+            Value::Range { start, end, step } => {
+                if self.index >= range_len(*start, *end, *step) {
+                    return None;
+                }
+                let item = range_item(*start, *step, self.index);
+                self.index += 1;
+                Some(item)
+            },
             _ => None 
         }
     }
 }
 
+fn range_len(start: i32, end: i32, step: i32) -> usize {
+    let (start, end, step) = (i64::from(start), i64::from(end), 
+                              i64::from(step));
+    let span = if step > 0 { end - start } else { start - end };
+    if span <= 0 {
+        return 0;
+    }
+    usize::try_from((span - 1) / step.abs() + 1).unwrap_or(0)
+}
+
+// The value at position idx of a range, which must be in bounds
+pub fn range_item(start: i32, step: i32, idx: usize) -> Value {
+    let v = i64::from(start) + i64::from(step) * idx as i64;
+    Value::Int{v: v as i32}
+}
+
 impl Clone for ValueIterator{
     fn clone(&self) -> Self {
         Self { value: self.value.clone(), index: self.index }
@@ -132,14 +539,32 @@ impl PartialEq for Value {
                 => l_f == r_f,
             (Value::Char { c: l_c }, Value::Char { c: r_c }) 
                 => l_c == r_c,
-            (Value::List { e: l_e }, Value::List { e: r_e }) => {
-                for (lhs, rhs) in l_e.iter().zip(r_e) {
-                    if lhs != rhs {
-                        return false
-                    }
-                }
-                true
+            (Value::List { e: l_e }, Value::List { e: r_e }) 
+                => shared_eq(l_e, r_e),
+            (Value::Map { m: l_m }, Value::Map { m: r_m }) 
+                => shared_eq(l_m, r_m),
+            // Ranges are equal when they give the same items
+            (Value::Range { start: l_s, end: l_e, step: l_st }, 
+             Value::Range { start: r_s, end: r_e, step: r_st }) => {
+                let len = range_len(*l_s, *l_e, *l_st);
+                len == range_len(*r_s, *r_e, *r_st)
+                    && (len == 0 || l_s == r_s && (len == 1 || l_st == r_st))
             },
+            (Value::StructDef { name: l_n, .. }, 
+             Value::StructDef { name: r_n, .. }) 
+                => l_n == r_n,
+            (Value::Struct { name: l_n, fields: l_f }, 
+             Value::Struct { name: r_n, fields: r_f }) 
+                => l_n == r_n && shared_eq(l_f, r_f),
+            (Value::EnumDef { name: l_n, .. }, 
+             Value::EnumDef { name: r_n, .. }) 
+                => l_n == r_n,
+            (Value::Enum { name: l_n, variant: l_var, values: l_v }, 
+             Value::Enum { name: r_n, variant: r_var, values: r_v }) 
+                => l_n == r_n && l_var == r_var && l_v == r_v,
+            (Value::Error { error: l_e }, Value::Error { error: r_e }) 
+                => l_e.kind == r_e.kind && l_e.message == r_e.message 
+                   && l_e.value == r_e.value,
             (Value::Float { f }, Value::Int { v }) 
                 => f64::from(*v) == *f,
             (Value::Int { v }, Value::Float { f })
@@ -151,6 +576,26 @@ impl PartialEq for Value {
     }
 }
 
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int { v: l_v }, Value::Int { v: r_v }) 
+                => l_v.partial_cmp(r_v),
+            (Value::Float { f: l_f }, Value::Float { f: r_f }) 
+                => l_f.partial_cmp(r_f),
+            (Value::Int { v }, Value::Float { f }) 
+                => f64::from(*v).partial_cmp(f),
+            (Value::Float { f }, Value::Int { v }) 
+                => f.partial_cmp(&f64::from(*v)),
+            (Value::Char { c: l_c }, Value::Char { c: r_c }) 
+                => l_c.partial_cmp(r_c),
+            (Value::Str { s: l_s }, Value::Str { s: r_s }) 
+                => l_s.partial_cmp(r_s),
+            _ => None
+        }
+    }
+}
+
 impl Clone for Value {
     fn clone(&self) -> Self {
         match self {
@@ -168,31 +613,64 @@ impl Clone for Value {
                 => Self::Char { c: *c },
             Self::List { e } 
                 => Self::List { e: e.clone() },
+            Self::Map { m } 
+                => Self::Map { m: m.clone() },
+            Self::Range { start, end, step } 
+                => Self::Range { start: *start, end: *end, step: *step },
+            Self::StructDef { name, fields } 
+                => Self::StructDef { name: name.clone(), 
+                                     fields: fields.clone() },
+            Self::Struct { name, fields } 
+                => Self::Struct { name: name.clone(), 
+                                  fields: fields.clone() },
+            Self::EnumDef { name, variants } 
+                => Self::EnumDef { name: name.clone(), 
+                                   variants: variants.clone() },
+            Self::Enum { name, variant, values } 
+                => Self::Enum { name: name.clone(), variant: variant.clone(), 
+                                values: values.clone() },
+            Self::VariantConstructor { name, variant, arity } 
+                => Self::VariantConstructor { name: name.clone(), 
+                                              variant: variant.clone(), 
+                                              arity: *arity },
             Self::Function { name, f } 
                 => Self::Function { name: name.clone(), f: *f },
-            Self::UserDefFunction { name, statements, 
-                                    arguments, return_expression 
-                                  } => Self::UserDefFunction { 
+            Self::UserDefFunction { name, statements, arguments, env } 
+                => Self::UserDefFunction { 
                     name: name.clone(), statements: statements.clone(), 
-                    arguments: arguments.clone(), 
-                    return_expression: return_expression.clone() },
+                    arguments: arguments.clone(), env: env.clone() },
+            Self::Error { error } 
+                => Self::Error { error: error.clone() },
         }
     }
 }
 
+// Int arithmetic that overflows yields Null, like Value::pow
 impl Add for Value {
     type Output = Value;
 
     fn add(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Value::Int { v: lv }, Value::Int { v: rv }) 
-                => Value::Int{v: lv + rv},
+                => lv.checked_add(rv).map_or(Value::Null, |v| Value::Int{v}),
             (Value::Float { f: lf }, Value::Float { f: rf }) 
                 => Value::Float{f: lf + rf},
             (Value::Int { v }, Value::Float { f }) 
                 => Value::Float{f: f64::from(v) + f},
             (Value::Float { f }, Value::Int { v }) 
                 => Value::Float{f: f + f64::from(v)},
+            (Value::Str { s: ls }, Value::Str { s: rs }) 
+                => Value::Str{s: ls + &rs},
+            (Value::Str { mut s }, Value::Char { c }) => {
+                s.push(c);
+                Value::Str{s}
+            },
+            (Value::Char { c }, Value::Str { s }) 
+                => Value::Str{s: format!("{}{}", c, s)},
+            (Value::List { e: le }, Value::List { e: re }) 
+                => Value::List{e: shared(
+                    [le.borrow().as_slice(), &re.borrow()].concat()
+                )},
             _ => Value::Null
         }
     }
@@ -202,13 +680,23 @@ impl AddAssign for Value {
     fn add_assign(&mut self, other: Self) {
         *self = match (&self, other) {
             (Value::Int { v: lv }, Value::Int { v: rv }) 
-                => Value::Int{v: *lv + rv},
+                => lv.checked_add(rv).map_or(Value::Null, |v| Value::Int{v}),
             (Value::Float { f: lf }, Value::Float { f: rf }) 
                 => Value::Float{f: *lf + rf},
             (Value::Int { v }, Value::Float { f }) 
                 => Value::Float{f: f64::from(*v) + f},
             (Value::Float { f }, Value::Int { v }) 
                 => Value::Float{f: *f + f64::from(v)},
+            (Value::Str { s: ls }, Value::Str { s: rs }) 
+                => Value::Str{s: format!("{}{}", ls, rs)},
+            (Value::Str { s }, Value::Char { c }) 
+                => Value::Str{s: format!("{}{}", s, c)},
+            (Value::Char { c }, Value::Str { s }) 
+                => Value::Str{s: format!("{}{}", c, s)},
+            (Value::List { e: le }, Value::List { e: re }) 
+                => Value::List{e: shared(
+                    [le.borrow().as_slice(), &re.borrow()].concat()
+                )},
             _ => Value::Null
         };
     }
@@ -220,7 +708,7 @@ impl Sub for Value {
     fn sub(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Value::Int { v: lv }, Value::Int { v: rv }) 
-                => Value::Int{v: lv - rv},
+                => lv.checked_sub(rv).map_or(Value::Null, |v| Value::Int{v}),
             (Value::Float { f: lf }, Value::Float { f: rf }) 
                 => Value::Float{f: lf - rf},
             (Value::Int { v }, Value::Float { f }) 
@@ -236,7 +724,7 @@ impl SubAssign for Value {
     fn sub_assign(&mut self, other: Self) {
         *self = match (&self, other) {
             (Value::Int { v: lv }, Value::Int { v: rv }) 
-                => Value::Int{v: *lv - rv},
+                => lv.checked_sub(rv).map_or(Value::Null, |v| Value::Int{v}),
             (Value::Float { f: lf }, Value::Float { f: rf }) 
                 => Value::Float{f: *lf - rf},
             (Value::Int { v }, Value::Float { f }) 
@@ -254,7 +742,7 @@ impl Div for Value {
     fn div(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Value::Int { v: lv }, Value::Int { v: rv }) 
-                => Value::Int{v: lv / rv},
+                => lv.checked_div(rv).map_or(Value::Null, |v| Value::Int{v}),
             (Value::Float { f: lf }, Value::Float { f: rf }) 
                 => Value::Float{f: lf / rf},
             (Value::Int { v }, Value::Float { f }) 
@@ -270,7 +758,7 @@ impl DivAssign for Value {
     fn div_assign(&mut self, other: Self) {
         *self = match (&self, other) {
             (Value::Int { v: lv }, Value::Int { v: rv }) 
-                => Value::Int{v: *lv / rv},
+                => lv.checked_div(rv).map_or(Value::Null, |v| Value::Int{v}),
             (Value::Float { f: lf }, Value::Float { f: rf }) 
                 => Value::Float{f: *lf / rf},
             (Value::Int { v }, Value::Float { f }) 
@@ -288,13 +776,17 @@ impl Mul for Value {
     fn mul(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Value::Int { v: lv }, Value::Int { v: rv }) 
-                => Value::Int{v: lv * rv},
+                => lv.checked_mul(rv).map_or(Value::Null, |v| Value::Int{v}),
             (Value::Float { f: lf }, Value::Float { f: rf }) 
                 => Value::Float{f: lf * rf},
             (Value::Int { v }, Value::Float { f }) 
                 => Value::Float{f: f64::from(v) * f},
             (Value::Float { f }, Value::Int { v }) 
                 => Value::Float{f: f * f64::from(v)},
+            (seq @ (Value::Str { .. } | Value::List { .. }), Value::Int { v }) 
+            | (Value::Int { v }, 
+               seq @ (Value::Str { .. } | Value::List { .. })) 
+                => seq.repeat(v),
             _ => Value::Null
         }
     }
@@ -304,13 +796,17 @@ impl MulAssign for Value {
     fn mul_assign(&mut self, other: Self) {
         *self = match (&self, other) {
             (Value::Int { v: lv }, Value::Int { v: rv }) 
-                => Value::Int{v: *lv + rv},
+                => lv.checked_mul(rv).map_or(Value::Null, |v| Value::Int{v}),
             (Value::Float { f: lf }, Value::Float { f: rf }) 
-                => Value::Float{f: *lf + rf},
+                => Value::Float{f: *lf * rf},
             (Value::Int { v }, Value::Float { f }) 
                 => Value::Float{f: f64::from(*v) * f},
             (Value::Float { f }, Value::Int { v }) 
                 => Value::Float{f: *f * f64::from(v)},
+            (seq @ (Value::Str { .. } | Value::List { .. }), Value::Int { v }) 
+                => seq.repeat(v),
+            (Value::Int { v }, seq @ (Value::Str { .. } | Value::List { .. })) 
+                => seq.repeat(*v),
             _ => Value::Null
         };
     }
@@ -322,13 +818,23 @@ impl Add for &Value {
     fn add(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Value::Int { v: lv }, Value::Int { v: rv }) 
-                => Value::Int{v: lv + rv},
+                => lv.checked_add(*rv).map_or(Value::Null, |v| Value::Int{v}),
             (Value::Float { f: lf }, Value::Float { f: rf }) 
                 => Value::Float{f: lf + rf},
             (Value::Int { v }, Value::Float { f }) 
                 => Value::Float{f: f64::from(*v) + f},
             (Value::Float { f }, Value::Int { v }) 
                 => Value::Float{f: f + f64::from(*v)},
+            (Value::Str { s: ls }, Value::Str { s: rs }) 
+                => Value::Str{s: format!("{}{}", ls, rs)},
+            (Value::Str { s }, Value::Char { c }) 
+                => Value::Str{s: format!("{}{}", s, c)},
+            (Value::Char { c }, Value::Str { s }) 
+                => Value::Str{s: format!("{}{}", c, s)},
+            (Value::List { e: le }, Value::List { e: re }) 
+                => Value::List{e: shared(
+                    [le.borrow().as_slice(), &re.borrow()].concat()
+                )},
             _ => Value::Null
         }
     }
@@ -340,7 +846,7 @@ impl Sub for &Value {
     fn sub(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Value::Int { v: lv }, Value::Int { v: rv }) 
-                => Value::Int{v: lv - rv},
+                => lv.checked_sub(*rv).map_or(Value::Null, |v| Value::Int{v}),
             (Value::Float { f: lf }, Value::Float { f: rf }) 
                 => Value::Float{f: lf - rf},
             (Value::Int { v }, Value::Float { f }) 
@@ -358,7 +864,7 @@ impl Div for &Value {
     fn div(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Value::Int { v: lv }, Value::Int { v: rv }) 
-                => Value::Int{v: lv / rv},
+                => lv.checked_div(*rv).map_or(Value::Null, |v| Value::Int{v}),
             (Value::Float { f: lf }, Value::Float { f: rf }) 
                 => Value::Float{f: lf / rf},
             (Value::Int { v }, Value::Float { f }) 
@@ -376,13 +882,47 @@ impl Mul for &Value {
     fn mul(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Value::Int { v: lv }, Value::Int { v: rv }) 
-                => Value::Int{v: lv * rv},
+                => lv.checked_mul(*rv).map_or(Value::Null, |v| Value::Int{v}),
             (Value::Float { f: lf }, Value::Float { f: rf }) 
                 => Value::Float{f: lf * rf},
             (Value::Int { v }, Value::Float { f }) 
                 => Value::Float{f: f64::from(*v) * f},
             (Value::Float { f }, Value::Int { v }) 
                 => Value::Float{f: f * f64::from(*v)},
+            (seq @ (Value::Str { .. } | Value::List { .. }), Value::Int { v }) 
+            | (Value::Int { v }, 
+               seq @ (Value::Str { .. } | Value::List { .. })) 
+                => seq.repeat(*v),
+            _ => Value::Null
+        }
+    }
+}
+
+// The remainder takes the sign of the divisor to stay consistent with
+// Value::floor_div. Overflow yields Null
+impl Rem for &Value {
+    type Output = Value;
+
+    fn rem(self, rhs: Self) -> Self::Output {
+        match (self, rhs) {
+            (Value::Int { v: lv }, Value::Int { v: rv }) => {
+                let Some(r) = lv.checked_rem(*rv) else { return Value::Null };
+                if r != 0 && (r < 0) != (*rv < 0) {
+                    Value::Int{v: r + rv}
+                } else {
+                    Value::Int{v: r}
+                }
+            },
+            (Value::Float { f: lf }, Value::Float { f: rf }) 
+                => Value::Float{f: lf - rf * (lf / rf).floor()},
+            (Value::Int { v }, Value::Float { f }) => {
+                let lf = f64::from(*v);
+                Value::Float{f: lf - f * (lf / f).floor()}
+            },
+            (Value::Float { f }, Value::Int { v }) => {
+                let rf = f64::from(*v);
+                Value::Float{f: f - rf * (f / rf).floor()}
+            },
             _ => Value::Null
         }
     }
@@ -4,9 +4,15 @@ use std::str;
 
 
 pub fn get_program_output(file: &str) -> (String, String){
+    get_program_output_with_flags(file, &[])
+}
+
+pub fn get_program_output_with_flags(file: &str, flags: &[&str]) 
+    -> (String, String){
     let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
-    path.push("target/debug/rustlang");
+    path.push("target/debug/brainstorm");
     let output = Command::new(path)
+        .args(flags)
         .arg(file)
         .output()
         .expect("Failed to execute command");
@@ -125,7 +125,8 @@ mod tests {
 
     #[test]
     fn test_import_env(){
-        env::set_var("RUSTL_LIB", "/home/nakul/projects/rustlang/tests/common");
+        env::set_var("RUSTL_LIB", 
+                     concat!(env!("CARGO_MANIFEST_DIR"), "/tests/common"));
 
         let(log, errors)
             = common::get_program_output("tests/test_sources/test_import_env.txt");
@@ -138,8 +139,17 @@ mod tests {
 
     #[test]
     fn test_import_absolute(){
+        // The import path has to be absolute, so the source is templated with
+        // the location of the crate and written out before running it
+        let source 
+            = common::read_file("tests/test_sources/test_import_absolute.txt")
+                .replace("{CARGO_MANIFEST_DIR}", env!("CARGO_MANIFEST_DIR"));
+        let path 
+            = format!("{}/test_import_absolute.txt", env!("CARGO_TARGET_TMPDIR"));
+        std::fs::write(&path, source).unwrap();
+
         let(log, errors)
-            = common::get_program_output("tests/test_sources/test_import_absolute.txt");
+            = common::get_program_output(&path);
         
         let expected_output
             = common::read_file("tests/test_output/test_import_absolute.output");
@@ -190,4 +200,427 @@ mod tests {
         
         assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
     }
+
+    #[test]
+    fn test_logical_operators(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_logical_operators.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_logical_operators.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_precedence(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_precedence.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_precedence.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_comparison(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_comparison.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_comparison.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_operators_modulo_power(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_operators_modulo_power.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_operators_modulo_power.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_division_by_zero(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_division_by_zero.txt");
+
+        assert!(log.starts_with("\nError: Division by zero in '%'\n"), 
+                "\nOutput:\n{}\nErrors:\n{}\n", log, errors);
+    }
+
+    #[test]
+    fn test_return(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_return.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_return.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_break_continue(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_break_continue.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_break_continue.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_break_outside_loop(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_break_outside_loop.txt");
+
+        assert!(log.starts_with("\nError: 'break' outside of a loop\n"), 
+                "\nOutput:\n{}\nErrors:\n{}\n", log, errors);
+    }
+
+    #[test]
+    fn test_lambda(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_lambda.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_lambda.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_call_expression(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_call_expression.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_call_expression.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_map(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_map.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_map.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_struct(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_struct.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_struct.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_struct_missing_field(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_struct_missing_field.txt");
+
+        assert!(log.starts_with("\nError: Struct 'Point' has no field 'z'\n"), 
+                "\nOutput:\n{}\nErrors:\n{}\n", log, errors);
+    }
+
+    #[test]
+    fn test_match(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_match.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_match.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_match_no_arm(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_match_no_arm.txt");
+
+        assert!(errors.contains("Warning: Match on enum 'Light' does not handle Yellow\n"), 
+                "\nErrors:\n{}\n", errors);
+        assert!(log.starts_with("Light.Green\n\nError: No match arm matches the value 'Light.Yellow'\n"), 
+                "\nOutput:\n{}\nErrors:\n{}\n", log, errors);
+    }
+
+    #[test]
+    fn test_escapes(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_escapes.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_escapes.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_invalid_escape(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_invalid_escape.txt");
+
+        assert!(log.starts_with("\nError: Invalid escape sequence '\\q' at line 2, column 9\n"), 
+                "\nOutput:\n{}\nErrors:\n{}\n", log, errors);
+    }
+
+    #[test]
+    fn test_fstring(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_fstring.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_fstring.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_concat_repeat(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_concat_repeat.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_concat_repeat.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_slices(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_slices.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_slices.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_nested_assignment(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_nested_assignment.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_nested_assignment.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_comprehension_clauses(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_comprehension_clauses.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_comprehension_clauses.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_range(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_range.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_range.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_range_zero_step(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_range_zero_step.txt");
+
+        assert!(log.starts_with("3\n2\n1\n\nError: range_step cannot have a step of zero\n"), 
+                "\nOutput:\n{}\nErrors:\n{}\n", log, errors);
+    }
+
+    #[test]
+    fn test_type_annotations(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_type_annotations.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_type_annotations.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_type_errors(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_type_errors.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_type_errors.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_strict(){
+        let(log, errors)
+            = common::get_program_output_with_flags(
+                "tests/test_sources/test_strict.txt", &["--strict"]);
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_strict.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_strict_import(){
+        let(log, errors)
+            = common::get_program_output_with_flags(
+                "tests/test_sources/test_strict_import.txt", &["--strict"]);
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_strict_import.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_import_type_error(){
+        let(log, errors)
+            = common::get_program_output_with_flags(
+                "tests/test_sources/test_import_type_error.txt", 
+                &["--strict"]);
+
+        assert!(log.starts_with("\nError: Expected int for 'bad', found str \
+                                 at line 1, column 1 in ./typed_helpers.txt\n\
+                                 Parameter 'value' of 'show' needs a type \
+                                 annotation at line 4, column 1 in \
+                                 ./typed_helpers.txt\n"), 
+                "\nOutput:\n{}\nErrors:\n{}\n", log, errors);
+        assert!(log.contains("Parameter 'a' of 'dup' is given more than \
+                              once at line 8, column 1 in \
+                              ./typed_helpers.txt\n"), 
+                "\nOutput:\n{}\nErrors:\n{}\n", log, errors);
+    }
+
+    #[test]
+    fn test_parameters(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_parameters.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_parameters.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_missing_argument(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_missing_argument.txt");
+
+        assert!(log.starts_with("9\n\nError: Missing argument 'width' for 'area'\n"), 
+                "\nOutput:\n{}\nErrors:\n{}\n", log, errors);
+    }
+
+    #[test]
+    fn test_spread_arguments(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_spread_arguments.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_spread_arguments.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_spread_key(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_spread_key.txt");
+
+        assert!(log.starts_with("1\n2\n\nError: Cannot spread the key '1' into a named argument\n"), 
+                "\nOutput:\n{}\nErrors:\n{}\n", log, errors);
+    }
+
+    #[test]
+    fn test_exceptions(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_exceptions.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_exceptions.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_uncaught_error(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_uncaught_error.txt");
+
+        assert!(log.starts_with("saving notes\nclosing\n\nError: disk is full\nIOError at line 3, column 5\n"), 
+                "\nOutput:\n{}\nErrors:\n{}\n", log, errors);
+    }
+
+    #[test]
+    fn test_scoping(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_scoping.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_scoping.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_declaration_errors(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_declaration_errors.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_declaration_errors.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_outer_variables(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_outer_variables.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_outer_variables.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
+
+    #[test]
+    fn test_references(){
+        let(log, errors)
+            = common::get_program_output("tests/test_sources/test_references.txt");
+        
+        let expected_output
+            = common::read_file("tests/test_output/test_references.output");
+        
+        assert_eq!(expected_output, log, "\nErrors:\n{}\n", errors);
+    }
 }
\ No newline at end of file